@@ -2,6 +2,68 @@
 
 use std::ops::{Index, IndexMut};
 
+/// Offsets of the orthogonal neighbours of a cell, in reading order.
+pub const OFFSETS4: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
+
+/// Offsets of all eight neighbours of a cell, in reading order.
+pub const OFFSETS8: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// A direction on the grid, with y growing downwards.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Dir {
+    Up,
+    Left,
+    Right,
+    Down,
+}
+
+impl Dir {
+    /// All directions, in reading order of the cells they lead to.
+    pub const ALL: [Dir; 4] = [Dir::Up, Dir::Left, Dir::Right, Dir::Down];
+
+    pub fn turn_left(self) -> Dir {
+        match self {
+            Dir::Up => Dir::Left,
+            Dir::Left => Dir::Down,
+            Dir::Down => Dir::Right,
+            Dir::Right => Dir::Up,
+        }
+    }
+
+    pub fn turn_right(self) -> Dir {
+        match self {
+            Dir::Up => Dir::Right,
+            Dir::Right => Dir::Down,
+            Dir::Down => Dir::Left,
+            Dir::Left => Dir::Up,
+        }
+    }
+
+    pub fn offset(self) -> (isize, isize) {
+        match self {
+            Dir::Up => (0, -1),
+            Dir::Left => (-1, 0),
+            Dir::Right => (1, 0),
+            Dir::Down => (0, 1),
+        }
+    }
+
+    /// The position one step away, unless that is left of or above 0.
+    pub fn step(self, x: usize, y: usize) -> Option<(usize, usize)> {
+        let (ox, oy) = self.offset();
+        Some((x.checked_add_signed(ox)?, y.checked_add_signed(oy)?))
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub struct Array2d<T> {
     data: Vec<T>,
@@ -103,6 +165,36 @@ impl<T> Array2d<T> {
         }
     }
 
+    /// The positions at `offsets` from `x`, `y` which lie inside the array.
+    /// The iterator doesn't borrow the array, so it can be changed meanwhile.
+    pub fn neighbors_in_bounds<'a>(
+        &self,
+        x: usize,
+        y: usize,
+        offsets: &'a [(isize, isize)],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let (w, h) = (self.width, self.height);
+        offsets.iter().filter_map(move |&(ox, oy)| {
+            let (x, y) = (x.checked_add_signed(ox)?, y.checked_add_signed(oy)?);
+            if x < w && y < h {
+                Some((x, y))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// The orthogonal neighbours of `x`, `y` inside the array, in reading
+    /// order.
+    pub fn neighbors4(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        self.neighbors_in_bounds(x, y, &OFFSETS4)
+    }
+
+    /// All neighbours of `x`, `y` inside the array, in reading order.
+    pub fn neighbors8(&self, x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
+        self.neighbors_in_bounds(x, y, &OFFSETS8)
+    }
+
     pub fn to_string<F>(&self, f: F) -> String
     where
         F: Fn(&T) -> char,
@@ -187,3 +279,31 @@ impl<T: Default> DynArray2d<T> {
         &mut row[x]
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn neighbors() {
+        let a: Array2d<u8> = Array2d::new(3, 2);
+        let n4: Vec<_> = a.neighbors4(1, 1).collect();
+        assert_eq!(n4, [(1, 0), (0, 1), (2, 1)]);
+        let n8: Vec<_> = a.neighbors8(0, 0).collect();
+        assert_eq!(n8, [(1, 0), (0, 1), (1, 1)]);
+        assert_eq!(a.neighbors8(1, 0).count(), 5);
+    }
+
+    #[test]
+    fn dir() {
+        for &dir in &Dir::ALL {
+            assert_eq!(dir.turn_left().turn_right(), dir);
+            assert_eq!(dir.turn_left().turn_left(), dir.turn_right().turn_right());
+        }
+        assert_eq!(Dir::Up.turn_right(), Dir::Right);
+        assert_eq!(Dir::Left.step(0, 3), None);
+        assert_eq!(Dir::Down.step(0, 3), Some((0, 4)));
+        let offsets: Vec<_> = Dir::ALL.iter().map(|d| d.offset()).collect();
+        assert_eq!(offsets, OFFSETS4);
+    }
+}
@@ -26,30 +26,133 @@ mod day7;
 mod day8;
 mod day9;
 
-pub const DAYS: &'static [fn(&AocData) -> AocResult] = &[
-    self::day1::run,
-    self::day2::run,
-    self::day3::run,
-    self::day4::run,
-    self::day5::run,
-    self::day6::run,
-    self::day7::run,
-    self::day8::run,
-    self::day9::run,
-    self::day10::run,
-    self::day11::run,
-    self::day12::run,
-    self::day13::run,
-    self::day14::run,
-    self::day15::run,
-    self::day16::run,
-    self::day17::run,
-    self::day18::run,
-    self::day19::run,
-    self::day20::run,
-    self::day21::run,
-    self::day22::run,
-    self::day23::run,
-    self::day24::run,
-    self::day25::run,
+pub struct Day {
+    pub run: fn(&AocData) -> AocResult,
+    pub examples: &'static [Example],
+}
+
+pub const DAYS: &[Day] = &[
+    Day {
+        run: self::day1::run,
+        examples: self::day1::EXAMPLES,
+    },
+    Day {
+        run: self::day2::run,
+        examples: self::day2::EXAMPLES,
+    },
+    Day {
+        run: self::day3::run,
+        examples: self::day3::EXAMPLES,
+    },
+    Day {
+        run: self::day4::run,
+        examples: self::day4::EXAMPLES,
+    },
+    Day {
+        run: self::day5::run,
+        examples: self::day5::EXAMPLES,
+    },
+    Day {
+        run: self::day6::run,
+        examples: self::day6::EXAMPLES,
+    },
+    Day {
+        run: self::day7::run,
+        examples: self::day7::EXAMPLES,
+    },
+    Day {
+        run: self::day8::run,
+        examples: self::day8::EXAMPLES,
+    },
+    Day {
+        run: self::day9::run,
+        examples: self::day9::EXAMPLES,
+    },
+    Day {
+        run: self::day10::run,
+        examples: self::day10::EXAMPLES,
+    },
+    Day {
+        run: self::day11::run,
+        examples: self::day11::EXAMPLES,
+    },
+    Day {
+        run: self::day12::run,
+        examples: self::day12::EXAMPLES,
+    },
+    Day {
+        run: self::day13::run,
+        examples: self::day13::EXAMPLES,
+    },
+    Day {
+        run: self::day14::run,
+        examples: self::day14::EXAMPLES,
+    },
+    Day {
+        run: self::day15::run,
+        examples: self::day15::EXAMPLES,
+    },
+    Day {
+        run: self::day16::run,
+        examples: &[],
+    },
+    Day {
+        run: self::day17::run,
+        examples: self::day17::EXAMPLES,
+    },
+    Day {
+        run: self::day18::run,
+        examples: self::day18::EXAMPLES,
+    },
+    Day {
+        run: self::day19::run,
+        examples: &[],
+    },
+    Day {
+        run: self::day20::run,
+        examples: self::day20::EXAMPLES,
+    },
+    Day {
+        run: self::day21::run,
+        examples: &[],
+    },
+    Day {
+        run: self::day22::run,
+        examples: self::day22::EXAMPLES,
+    },
+    Day {
+        run: self::day23::run,
+        examples: self::day23::EXAMPLES,
+    },
+    Day {
+        run: self::day24::run,
+        examples: self::day24::EXAMPLES,
+    },
+    Day {
+        run: self::day25::run,
+        examples: self::day25::EXAMPLES,
+    },
 ];
+
+#[cfg(test)]
+#[test]
+fn examples() {
+    use crate::runner::solve_example;
+    use crate::verify::check_example;
+
+    for &part in &[None, Some(Part::First), Some(Part::Second)] {
+        for (i, day) in DAYS.iter().enumerate() {
+            for example in day.examples.iter().filter(|e| e.covers(part)) {
+                let (answers, _) = solve_example(i + 1, example, part).unwrap();
+                let diffs = check_example(example, &answers, part);
+                assert!(
+                    diffs.is_empty(),
+                    "Day {} ({:?}): {}",
+                    i + 1,
+                    part,
+                    diffs.join("\n")
+                );
+            }
+        }
+    }
+}
@@ -3,16 +3,33 @@ use std::collections::HashSet;
 
 pub fn run(data: &AocData) -> AocResult {
     let changes: Vec<i32> = data.values()?.collect();
-    let first: i32 = changes.iter().sum();
-    let mut seen: HashSet<i32> = HashSet::new();
-    let mut freq = 0i32;
-    let second = 'outer: loop {
-        for &change in &changes {
-            if !seen.insert(freq) {
-                break 'outer freq;
-            }
-            freq += change;
-        }
-    };
-    answers(first, second)
+    parts(
+        data,
+        || Ok(changes.iter().sum::<i32>()),
+        || {
+            let mut seen: HashSet<i32> = HashSet::new();
+            let mut freq = 0i32;
+            Ok('outer: loop {
+                for &change in &changes {
+                    if !seen.insert(freq) {
+                        break 'outer freq;
+                    }
+                    freq += change;
+                }
+            })
+        },
+    )
 }
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: "+1\n-2\n+3\n+1",
+        first: Some("3"),
+        second: Some("2"),
+    },
+    Example {
+        input: "+7\n+7\n-2\n-7\n-4",
+        first: None,
+        second: Some("14"),
+    },
+];
@@ -1,6 +1,7 @@
+use crate::array2d::Array2d;
+use crate::ocr;
 use crate::prelude::*;
 use std::cmp;
-use std::i32;
 
 struct Point {
     pos: (i32, i32),
@@ -46,49 +47,149 @@ impl BoundingRect {
     }
 }
 
-pub fn run(data: &AocData) -> AocResult {
-    let mut points: Vec<Point> = Vec::new();
-    let re =
-        Regex::new(r"^position=<\s*(-?\d+),\s*(-?\d+)> velocity=<\s*(-?\d+),\s*(-?\d+)>$").unwrap();
-
-    for line in data.lines()? {
-        if let Some(cap) = re.captures(&line) {
-            points.push(Point {
-                pos: (cap[1].parse().unwrap(), cap[2].parse().unwrap()),
-                vel: (cap[3].parse().unwrap(), cap[4].parse().unwrap()),
-            });
-        } else {
-            bail!("Failed to parse line: {}", line);
-        }
+fn bounding_rect(points: &[Point], time: i32) -> BoundingRect {
+    let mut rect = BoundingRect::new();
+    for p in points {
+        rect.add(p.at_time(time));
     }
+    rect
+}
 
-    let mut rect = BoundingRect::new();
-    let mut time = 0;
-    loop {
-        let mut new_rect = BoundingRect::new();
-        for p in &points {
-            new_rect.add(p.at_time(time));
-        }
-        if time > 0 && rect.area() < new_rect.area() {
-            break;
-        }
-        rect = new_rect;
+/// Finds the time at which the points are closest together. The spread of
+/// the points is a quadratic in time, so its minimum gives a good first
+/// guess, which is then refined by following the bounding area downhill.
+fn convergence_time(points: &[Point]) -> Result<i32> {
+    let n = points.len() as f64;
+    let mean = |f: &dyn Fn(&Point) -> i32| points.iter().map(|p| f(p) as f64).sum::<f64>() / n;
+    let mean_pos = (mean(&|p| p.pos.0), mean(&|p| p.pos.1));
+    let mean_vel = (mean(&|p| p.vel.0), mean(&|p| p.vel.1));
+    let mut cov = 0.0;
+    let mut var = 0.0;
+    for p in points {
+        let pos = (p.pos.0 as f64 - mean_pos.0, p.pos.1 as f64 - mean_pos.1);
+        let vel = (p.vel.0 as f64 - mean_vel.0, p.vel.1 as f64 - mean_vel.1);
+        cov += pos.0 * vel.0 + pos.1 * vel.1;
+        var += vel.0 * vel.0 + vel.1 * vel.1;
+    }
+
+    if var == 0.0 {
+        bail!("Points all move together, the bounding area never shrinks");
+    }
+
+    let area = |time: i32| bounding_rect(points, time).area();
+    let max_time = max_time(points);
+    let mut time = (-cov / var).round().max(0.0).min(max_time as f64) as i32;
+    while time > 0 && area(time - 1) < area(time) {
+        time -= 1;
+    }
+    while time < max_time && area(time + 1) < area(time) {
         time += 1;
     }
-    time -= 1;
+    if time == max_time && (time == 0 || area(time - 1) > area(time)) {
+        bail!("Bounding area still shrinks when the coordinates overflow");
+    }
+    Ok(time)
+}
+
+/// The latest time up to which the coordinates of all points, and the
+/// distances between them, fit in an `i32`.
+fn max_time(points: &[Point]) -> i32 {
+    let limit = i64::from(i32::MAX / 2);
+    points
+        .iter()
+        .flat_map(|p| [(p.pos.0, p.vel.0), (p.pos.1, p.vel.1)])
+        .filter(|&(_, vel)| vel != 0)
+        .map(|(pos, vel)| (limit - i64::from(pos).abs()).max(0) / i64::from(vel).abs())
+        .min()
+        .unwrap_or(0) as i32
+}
+
+pub fn run(data: &AocData) -> AocResult {
+    let points: Vec<Point> = data
+        .records(r"^position=<\s*(-?\d+),\s*(-?\d+)> velocity=<\s*(-?\d+),\s*(-?\d+)>$")?
+        .map(|(px, py, vx, vy)| Point {
+            pos: (px, py),
+            vel: (vx, vy),
+        })
+        .collect();
+    if points.is_empty() {
+        bail!("No points found in input");
+    }
+
+    let time = convergence_time(&points)?;
+    parts(data, || Ok(message(&points, time)), || Ok(time))
+}
 
-    let mut s = vec!['.'; rect.area()];
-    for p in &points {
+fn message(points: &[Point], time: i32) -> Answer {
+    let rect = bounding_rect(points, time);
+
+    let mut image = Array2d::new(rect.width(), rect.height());
+    for p in points {
         let (x, y) = p.at_time(time);
-        s[(x - rect.min.0) as usize + (y - rect.min.1) as usize * rect.width()] = '#';
+        image.put((x - rect.min.0) as usize, (y - rect.min.1) as usize, true);
     }
 
-    let mut result = String::with_capacity((rect.width() + 1) * rect.height() - 1);
-    for y in 0..rect.height() {
-        result.push('\n');
-        let o = y * rect.width();
-        result.extend(s[o..(o + rect.width())].iter().cloned());
+    // Fall back to the picture itself if it isn't written in the usual font.
+    match ocr::recognize(&image) {
+        Ok(text) => Answer::from(text),
+        Err(_) => Answer::grid(image.to_string(|&lit| if lit { '#' } else { '.' })),
     }
+}
+
+pub const EXAMPLES: &[Example] = &[Example {
+    input: "
+position=< 9,  1> velocity=< 0,  2>
+position=< 7,  0> velocity=<-1,  0>
+position=< 3, -2> velocity=<-1,  1>
+position=< 6, 10> velocity=<-2, -1>
+position=< 2, -4> velocity=< 2,  2>
+position=<-6, 10> velocity=< 2, -2>
+position=< 1,  8> velocity=< 1, -1>
+position=< 1,  7> velocity=< 1,  0>
+position=<-3, 11> velocity=< 1, -2>
+position=< 7,  6> velocity=<-1, -1>
+position=<-2,  3> velocity=< 1,  0>
+position=<-4,  3> velocity=< 2,  0>
+position=<10, -3> velocity=<-1,  1>
+position=< 5, 11> velocity=< 1, -2>
+position=< 4,  7> velocity=< 0, -1>
+position=< 8, -2> velocity=< 0,  1>
+position=<15,  0> velocity=<-2,  0>
+position=< 1,  6> velocity=< 1,  0>
+position=< 8,  9> velocity=< 0, -1>
+position=< 3,  3> velocity=<-1,  1>
+position=< 0,  5> velocity=< 0, -1>
+position=<-2,  2> velocity=< 2,  0>
+position=< 5, -2> velocity=< 1,  2>
+position=< 1,  4> velocity=< 2,  1>
+position=<-2,  7> velocity=< 2, -2>
+position=< 3,  6> velocity=<-1, -1>
+position=< 5,  0> velocity=< 1,  0>
+position=<-6,  0> velocity=< 2,  0>
+position=< 5,  9> velocity=< 1, -2>
+position=<14,  7> velocity=<-2,  0>
+position=<-3,  6> velocity=< 2, -1>",
+    first: None,
+    second: Some("3"),
+}];
 
-    answers(result, time)
+#[cfg(test)]
+#[test]
+fn test() {
+    let points = |list: &[(i32, i32)]| -> Vec<Point> {
+        list.iter()
+            .map(|&(x, vx)| Point {
+                pos: (x, 0),
+                vel: (vx, 0),
+            })
+            .collect()
+    };
+    // Already as close as they get.
+    assert_eq!(convergence_time(&points(&[(0, 1), (1, 2)])).unwrap(), 0);
+    assert_eq!(convergence_time(&points(&[(0, 1), (10, -1)])).unwrap(), 5);
+    // The area never changes.
+    assert!(convergence_time(&points(&[(0, 3), (5, 3)])).is_err());
+    // These would only meet after the coordinates overflow.
+    let far = points(&[(0, 1000), (2_000_000_000, 0)]);
+    assert!(convergence_time(&far).is_err());
 }
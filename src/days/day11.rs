@@ -1,9 +1,27 @@
 use crate::prelude::*;
 
-pub fn run(_: &AocData) -> AocResult {
-    answers(best_square(1723), best_square_any_size(1723))
+pub fn run(data: &AocData) -> AocResult {
+    let grid_serial: u32 = data.to_string()?.trim().parse()?;
+    parts(
+        data,
+        || Ok(best_square(grid_serial)),
+        || Ok(best_square_any_size(grid_serial)),
+    )
 }
 
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: "18",
+        first: Some("(33, 45)"),
+        second: Some("(90, 269, 16)"),
+    },
+    Example {
+        input: "42",
+        first: Some("(21, 61)"),
+        second: Some("(232, 251, 12)"),
+    },
+];
+
 fn power_level(grid_serial: u32, x: u32, y: u32) -> i32 {
     let rack_id = x + 10;
     let power = rack_id * y;
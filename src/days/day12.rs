@@ -45,13 +45,15 @@ impl Pots {
     }
 }
 
-fn parse_input(lines: &mut Iterator<Item = String>) -> Result<(Pots, Rules)> {
-    let initial_state = lines
-        .next()
-        .ok_or_else(|| format_err!("initial state missing"))?;
+fn parse_input(data: &AocData) -> Result<(Pots, Rules)> {
+    let sections = data.sections()?;
+    let (initial_state, rule_lines) = match sections.as_slice() {
+        [initial_state, rules] if initial_state.len() == 1 => (&initial_state[0], rules),
+        _ => bail!("Expected initial state and rules separated by an empty line"),
+    };
     let pots = if let Some(cap) = Regex::new(r"^initial state: ([#.]+)$")
         .unwrap()
-        .captures(&initial_state)
+        .captures(initial_state)
     {
         Pots {
             first: 0,
@@ -61,14 +63,10 @@ fn parse_input(lines: &mut Iterator<Item = String>) -> Result<(Pots, Rules)> {
         bail!("Failed to parse initial state: {}", initial_state);
     };
 
-    if !lines.next().iter().any(|s| s == "") {
-        bail!("Expected empty line after initial state");
-    }
-
     let re = Regex::new(r"^([#.]{5}) => ([#.])$").unwrap();
     let mut rules = 0u32;
-    for line in lines {
-        if let Some(cap) = re.captures(&line) {
+    for line in rule_lines {
+        if let Some(cap) = re.captures(line) {
             if &cap[2] == "#" {
                 let i = cap[1]
                     .chars()
@@ -111,11 +109,35 @@ fn flower_sum(pots: &Pots, rules: Rules, generations: usize) -> isize {
     unreachable!();
 }
 
-pub fn run(data: &AocData) -> AocResult {
-    let (pots, rules) = parse_input(&mut data.lines()?)?;
+// The puzzle gives no answer for the second part of the example.
+pub const EXAMPLES: &[Example] = &[Example {
+    input: "
+initial state: #..#.#..##......###...###
+
+...## => #
+..#.. => #
+.#... => #
+.#.#. => #
+.#.## => #
+.##.. => #
+.#### => #
+#.#.# => #
+#.### => #
+##.#. => #
+##.## => #
+###.. => #
+###.# => #
+####. => #",
+    first: Some("325"),
+    second: None,
+}];
 
-    let flowers_20 = flower_sum(&pots, rules, 20);
-    let flowers_50000000000 = flower_sum(&pots, rules, 50000000000);
+pub fn run(data: &AocData) -> AocResult {
+    let (pots, rules) = parse_input(data)?;
 
-    answers(flowers_20, flowers_50000000000)
+    parts(
+        data,
+        || Ok(flower_sum(&pots, rules, 20)),
+        || Ok(flower_sum(&pots, rules, 50000000000)),
+    )
 }
@@ -1,3 +1,4 @@
+use crate::array2d::Array2d;
 use crate::prelude::*;
 use std::collections::BTreeSet;
 
@@ -10,6 +11,12 @@ enum Track {
     Intersection,
 }
 
+impl Default for Track {
+    fn default() -> Track {
+        Track::None
+    }
+}
+
 #[derive(Clone)]
 struct Cart {
     x: usize,
@@ -27,21 +34,21 @@ enum BlockedState {
 
 #[derive(Clone)]
 struct Map {
-    tracks: Vec<Vec<Track>>,
-    blocked: Vec<Vec<BlockedState>>,
+    tracks: Array2d<Track>,
+    blocked: Array2d<BlockedState>,
 }
 
 impl Map {
     fn track(&self, x: usize, y: usize) -> Track {
-        self.tracks[y][x]
+        self.tracks[(x, y)]
     }
 
     fn get_blocked(&self, x: usize, y: usize) -> BlockedState {
-        self.blocked[y][x]
+        self.blocked[(x, y)]
     }
 
     fn set_blocked(&mut self, x: usize, y: usize, v: BlockedState) {
-        self.blocked[y][x] = v;
+        self.blocked[(x, y)] = v;
     }
 }
 
@@ -85,48 +92,41 @@ impl Cart {
     }
 }
 
-fn parse_input(lines: &mut Iterator<Item = String>) -> Result<(Map, Vec<Cart>)> {
-    let mut tracks = vec![];
-    let mut blocked = vec![];
-    let mut carts = vec![];
-    for (y, line) in lines.enumerate() {
-        let mut tracks_row = vec![];
-        let mut blocked_row = vec![];
-        for (x, c) in line.chars().enumerate() {
-            let (track, blocked) = match c {
-                ' ' => (Track::None, false),
-                '-' | '|' => (Track::Straight, false),
-                '/' => (Track::Slash, false),
-                '\\' => (Track::Backslash, false),
-                '+' => (Track::Intersection, false),
-                '^' | '>' | 'v' | '<' => {
-                    let dir = match c {
-                        '^' => 0,
-                        '>' => 1,
-                        'v' => 2,
-                        '<' => 3,
-                        _ => unreachable!(),
-                    };
-                    carts.push(Cart {
-                        x,
-                        y,
-                        dir,
-                        turn_counter: 0,
-                    });
-                    (Track::Straight, true)
-                }
-                _ => bail!("Unexpected char '{}'", c),
-            };
-            tracks_row.push(track);
-            blocked_row.push(if blocked {
-                BlockedState::Blocked
-            } else {
-                BlockedState::Free
-            });
+fn parse_input(data: &AocData) -> Result<(Map, Vec<Cart>)> {
+    let tiles = data.grid(|c| {
+        Ok(match c {
+            ' ' => (Track::None, None),
+            '-' | '|' => (Track::Straight, None),
+            '/' => (Track::Slash, None),
+            '\\' => (Track::Backslash, None),
+            '+' => (Track::Intersection, None),
+            '^' => (Track::Straight, Some(0)),
+            '>' => (Track::Straight, Some(1)),
+            'v' => (Track::Straight, Some(2)),
+            '<' => (Track::Straight, Some(3)),
+            _ => bail!("Unexpected char '{}'", c),
+        })
+    })?;
+    let carts = tiles
+        .iter_xy()
+        .filter_map(|(x, y)| {
+            tiles[(x, y)].1.map(|dir| Cart {
+                x,
+                y,
+                dir,
+                turn_counter: 0,
+            })
+        })
+        .collect();
+    let (width, height) = (tiles.width(), tiles.height());
+    let tracks = Array2d::new_with(width, height, |x, y| tiles[(x, y)].0);
+    let blocked = Array2d::new_with(width, height, |x, y| {
+        if tiles[(x, y)].1.is_some() {
+            BlockedState::Blocked
+        } else {
+            BlockedState::Free
         }
-        tracks.push(tracks_row);
-        blocked.push(blocked_row);
-    }
+    });
     Ok((Map { tracks, blocked }, carts))
 }
 
@@ -174,12 +174,38 @@ fn last_alive(mut map: Map, mut carts: Vec<Cart>) -> (usize, usize) {
     (0, 0)
 }
 
-pub fn run(data: &AocData) -> AocResult {
-    let (map, carts) = parse_input(&mut data.lines()?)?;
-
-    let crash = first_crash(map.clone(), carts.clone());
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: r"
+/->-\
+|   |  /----\
+| /-+--+-\  |
+| | |  | v  |
+\-+-/  \-+--/
+  \------/",
+        first: Some("(7, 3)"),
+        second: None,
+    },
+    Example {
+        input: r"
+/>-<\
+|   |
+| /<+-\
+| | | v
+\>+</ |
+  |   ^
+  \<->/",
+        first: None,
+        second: Some("(6, 4)"),
+    },
+];
 
-    let last_alive = last_alive(map, carts);
+pub fn run(data: &AocData) -> AocResult {
+    let (map, carts) = parse_input(data)?;
 
-    answers(crash, last_alive)
+    parts(
+        data,
+        || Ok(first_crash(map.clone(), carts.clone())),
+        || Ok(last_alive(map.clone(), carts.clone())),
+    )
 }
@@ -1,9 +1,41 @@
 use crate::prelude::*;
 
-pub fn run(_: &AocData) -> AocResult {
-    answers(scores_after(165061, 10), num_recipes_before("165061"))
+pub fn run(data: &AocData) -> AocResult {
+    let input = data.to_string()?;
+    let input = input.trim();
+    if input.is_empty() || !input.chars().all(|c| c.is_ascii_digit()) {
+        bail!("Failed to parse '{}'", input);
+    }
+    parts(
+        data,
+        || Ok(scores_after(input.parse()?, 10)),
+        || Ok(num_recipes_before(input)),
+    )
 }
 
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: "9",
+        first: Some("5158916779"),
+        second: None,
+    },
+    Example {
+        input: "2018",
+        first: Some("5941429882"),
+        second: None,
+    },
+    Example {
+        input: "51589",
+        first: None,
+        second: Some("9"),
+    },
+    Example {
+        input: "59414",
+        first: None,
+        second: Some("2018"),
+    },
+];
+
 struct Scoreboard {
     scores: Vec<u8>,
     i: usize,
@@ -3,15 +3,53 @@ use crate::prelude::*;
 
 pub fn run(data: &AocData) -> AocResult {
     let map = load_map(data)?;
-    let mut simulation = Simulation::new(map.clone(), 3);
-    simulation.run();
-
-    let needed_ap = find_needed_ap(map.clone());
-    let mut winning_simulation = Simulation::new(map, needed_ap);
-    winning_simulation.run();
-    answers(simulation.outcome(), winning_simulation.outcome())
+    parts(
+        data,
+        || {
+            let mut simulation = Simulation::new(map.clone(), 3);
+            simulation.run();
+            Ok(simulation.outcome())
+        },
+        || {
+            let needed_ap = find_needed_ap(map.clone());
+            let mut winning_simulation = Simulation::new(map.clone(), needed_ap);
+            winning_simulation.run();
+            Ok(winning_simulation.outcome())
+        },
+    )
 }
 
+const EXAMPLE1: &str = "
+#######
+#.G...#
+#...EG#
+#.#.#G#
+#..G#E#
+#.....#
+#######";
+
+const EXAMPLE2: &str = "
+#######
+#G..#E#
+#E#E.E#
+#G.##.#
+#...#E#
+#...E.#
+#######";
+
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: EXAMPLE1,
+        first: Some("27730"),
+        second: Some("4988"),
+    },
+    Example {
+        input: EXAMPLE2,
+        first: Some("36334"),
+        second: None,
+    },
+];
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Tile {
     Empty,
@@ -26,28 +64,21 @@ impl Default for Tile {
 }
 
 fn load_map(data: &AocData) -> Result<Array2d<Tile>> {
-    let lines: Vec<String> = data.lines()?.collect();
-    if lines.is_empty() {
+    let map = data.grid(|c| {
+        Ok(match c {
+            '.' => Tile::Empty,
+            '#' => Tile::Wall,
+            'G' => Tile::Fighter {
+                elf: false,
+                hp: 200,
+            },
+            'E' => Tile::Fighter { elf: true, hp: 200 },
+            _ => bail!("Unexpected char in map: '{}'", c),
+        })
+    })?;
+    if map.height() == 0 {
         bail!("Empty map");
     }
-    let mut map = Array2d::new(lines[0].len(), lines.len());
-
-    for (y, line) in lines.into_iter().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            let tile = match c {
-                '.' => Tile::Empty,
-                '#' => Tile::Wall,
-                'G' => Tile::Fighter {
-                    elf: false,
-                    hp: 200,
-                },
-                'E' => Tile::Fighter { elf: true, hp: 200 },
-                _ => bail!("Unexpected char in map: '{}'", c),
-            };
-            map.put(x, y, tile);
-        }
-    }
-
     Ok(map)
 }
 
@@ -199,7 +230,7 @@ impl Simulation {
             match self.map[(x, y)] {
                 Tile::Fighter { elf, .. } if elf == elves => {
                     found_targets = true;
-                    for (x, y) in neighbors(x, y) {
+                    for (x, y) in self.map.neighbors4(x, y) {
                         self.targets.put(x, y, true);
                     }
                 }
@@ -214,7 +245,7 @@ impl Simulation {
             return None;
         }
         self.distances.fill(0);
-        for (x, y) in neighbors(x, y) {
+        for (x, y) in self.map.neighbors4(x, y) {
             if self.map.get(x, y) == Tile::Empty {
                 self.distances.put(x, y, 1);
                 if self.targets.get(x, y) {
@@ -228,7 +259,7 @@ impl Simulation {
             for (x, y) in self.map.iter_xy() {
                 if self.distances.get(x, y) == d {
                     found = true;
-                    for (x, y) in neighbors(x, y) {
+                    for (x, y) in self.map.neighbors4(x, y) {
                         if self.map.get(x, y) == Tile::Empty && self.distances.get(x, y) == 0 {
                             self.distances.put(x, y, d + 1);
                             if self.targets.get(x, y) {
@@ -261,7 +292,7 @@ impl Simulation {
                     if dist == 1 {
                         return (x, y);
                     }
-                    for (x, y) in neighbors(x, y) {
+                    for (x, y) in self.map.neighbors4(x, y) {
                         self.targets.put(x, y, true);
                     }
                 }
@@ -271,14 +302,16 @@ impl Simulation {
     }
 
     fn attack(&mut self, x: usize, y: usize, elves: bool) {
-        if let Some(min_hp) = neighbors(x, y)
+        if let Some(min_hp) = self
+            .map
+            .neighbors4(x, y)
             .filter_map(|(x, y)| match self.map.get(x, y) {
                 Tile::Fighter { hp, elf } if elf == elves => Some(hp),
                 _ => None,
             })
             .min()
         {
-            for (x, y) in neighbors(x, y) {
+            for (x, y) in self.map.neighbors4(x, y) {
                 match self.map.get(x, y) {
                     Tile::Fighter { hp, elf } if hp == min_hp && elf == elves => {
                         let new_hp = hp.saturating_sub(if !elf { self.ap_elves } else { 3 });
@@ -300,14 +333,6 @@ impl Simulation {
     }
 }
 
-const OFFSETS: [(isize, isize); 4] = [(0, -1), (-1, 0), (1, 0), (0, 1)];
-
-fn neighbors(x: usize, y: usize) -> impl Iterator<Item = (usize, usize)> {
-    OFFSETS
-        .iter()
-        .map(move |&(ox, oy)| (((x as isize) + ox) as usize, ((y as isize) + oy) as usize))
-}
-
 #[cfg(test)]
 mod test {
     use super::*;
@@ -321,33 +346,11 @@ mod test {
 
     #[test]
     fn case0() {
-        run_testcase(
-            "
-#######
-#.G...#
-#...EG#
-#.#.#G#
-#..G#E#
-#.....#
-#######
-        ",
-            27730,
-        );
+        run_testcase(EXAMPLE1, 27730);
     }
 
     #[test]
     fn case1() {
-        run_testcase(
-            "
-#######
-#G..#E#
-#E#E.E#
-#G.##.#
-#...#E#
-#...E.#
-#######
-        ",
-            36334,
-        );
+        run_testcase(EXAMPLE2, 36334);
     }
 }
@@ -1,4 +1,4 @@
-use crate::array2d::Array2d;
+use crate::array2d::{Array2d, Dir};
 use crate::prelude::*;
 use std::cmp;
 use std::ops::RangeInclusive;
@@ -7,10 +7,28 @@ use std::usize;
 pub fn run(data: &AocData) -> AocResult {
     let (mut map, offsets) = parse_input(data)?;
     put_water(&mut map, offsets.spring, 0);
+    // Both parts count the water of the same simulation, so asking for only
+    // one of them doesn't save any work.
     let (reachable, resting) = count_water(&map, &offsets);
     answers(reachable + resting, resting)
 }
 
+const EXAMPLE: &str = "
+x=495, y=2..7
+y=7, x=495..501
+x=501, y=3..7
+x=498, y=2..4
+x=506, y=1..2
+x=498, y=10..13
+x=504, y=10..13
+y=13, x=498..504";
+
+pub const EXAMPLES: &[Example] = &[Example {
+    input: EXAMPLE,
+    first: Some("57"),
+    second: Some("29"),
+}];
+
 #[derive(Clone, Copy, Eq, PartialEq, Debug)]
 enum Tile {
     Sand,
@@ -32,29 +50,16 @@ struct MapOffsets {
 }
 
 fn parse_input(data: &AocData) -> Result<(Array2d<Tile>, MapOffsets)> {
-    let mut rects = vec![];
-    let re = Regex::new(r"(x|y)=(\d+)(\.\.(\d+))?").unwrap();
-
-    for line in data.lines()? {
-        let mut x: Option<RangeInclusive<usize>> = None;
-        let mut y: Option<RangeInclusive<usize>> = None;
-        for caps in re.captures_iter(&line) {
-            let from: usize = caps[2].parse().unwrap();
-            let range = if let Some(cap) = caps.get(4) {
-                from..=(cap.as_str().parse().unwrap())
-            } else {
-                from..=from
-            };
-            if &caps[1] == "x" {
-                x = Some(range);
-            } else {
-                y = Some(range);
-            }
+    let mut rects: Vec<(RangeInclusive<usize>, RangeInclusive<usize>)> = vec![];
+    let re = r"^([xy])=(\d+), ([xy])=(\d+)\.\.(\d+)$";
+    for (axis, pos, other, from, to) in data.records::<(char, usize, char, usize, usize)>(re)? {
+        if axis == other {
+            bail!("Vein gives the {} axis twice", axis);
         }
-        if let (Some(x), Some(y)) = (x, y) {
-            rects.push((x, y));
+        if axis == 'x' {
+            rects.push((pos..=pos, from..=to));
         } else {
-            bail!("Failed to parse: {}", line);
+            rects.push((from..=to, pos..=pos));
         }
     }
 
@@ -109,15 +114,14 @@ fn put_water(map: &mut Array2d<Tile>, x: usize, y: usize) -> bool {
         _ => (),
     }
 
-    fn walk<F>(map: &mut Array2d<Tile>, mut x: usize, y: usize, f: F) -> (usize, bool)
-    where
-        F: Fn(usize) -> usize,
-    {
+    fn walk(map: &mut Array2d<Tile>, mut x: usize, y: usize, dir: Dir) -> (usize, bool) {
         loop {
-            if map.get(f(x), y) == Tile::Clay {
+            // The map has a margin of sand, so water never reaches x = 0.
+            let (next_x, _) = dir.step(x, y).unwrap();
+            if map.get(next_x, y) == Tile::Clay {
                 return (x, true);
             }
-            x = f(x);
+            x = next_x;
             match map.get(x, y + 1) {
                 Tile::Sand => {
                     if !put_water(map, x, y + 1) {
@@ -130,8 +134,8 @@ fn put_water(map: &mut Array2d<Tile>, x: usize, y: usize) -> bool {
         }
     }
 
-    let (left_x, blocked_left) = walk(map, x, y, |x| x - 1);
-    let (right_x, blocked_right) = walk(map, x, y, |x| x + 1);
+    let (left_x, blocked_left) = walk(map, x, y, Dir::Left);
+    let (right_x, blocked_right) = walk(map, x, y, Dir::Right);
 
     let tile = if blocked_left && blocked_right {
         Tile::Resting
@@ -176,18 +180,7 @@ fn print_map(map: &Array2d<Tile>) {
 #[cfg(test)]
 #[test]
 fn test() {
-    let data = AocData::from_str(
-        "
-x=495, y=2..7
-y=7, x=495..501
-x=501, y=3..7
-x=498, y=2..4
-x=506, y=1..2
-x=498, y=10..13
-x=504, y=10..13
-y=13, x=498..504
-    ",
-    );
+    let data = AocData::from_str(EXAMPLE);
 
     let (mut map, offsets) = parse_input(&data).unwrap();
 
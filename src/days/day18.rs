@@ -6,13 +6,32 @@ use std::hash::{Hash, Hasher};
 use std::mem;
 
 pub fn run(data: &AocData) -> AocResult {
-    let mut field = parse_data(data)?;
-    let mut field2 = field.clone();
-    let resources10 = resource_value_after(&mut field, 10);
-    let resources1000000000 = resource_value_after(&mut field2, 1000000000);
-    answers(resources10, resources1000000000)
+    let field = parse_data(data)?;
+    parts(
+        data,
+        || Ok(resource_value_after(&mut field.clone(), 10)),
+        || Ok(resource_value_after(&mut field.clone(), 1000000000)),
+    )
 }
 
+const EXAMPLE: &str = "
+.#.#...|#.
+.....#|##|
+.|..|...#.
+..|#.....#
+#.#|||#|#|
+...#.||...
+.|....|...
+||...#|.#|
+|.||||..|.
+...#.|..|.";
+
+pub const EXAMPLES: &[Example] = &[Example {
+    input: EXAMPLE,
+    first: Some("1147"),
+    second: None,
+}];
+
 #[derive(Copy, Clone, Eq, PartialEq, Hash)]
 enum Tile {
     None,
@@ -30,24 +49,14 @@ impl Default for Tile {
 type Field = Array2d<Tile>;
 
 fn parse_data(data: &AocData) -> Result<Field> {
-    let lines: Vec<String> = data.lines()?.collect();
-    let width = lines.iter().map(|l| l.len()).max().unwrap_or(0);
-    let mut map = Array2d::new(width, lines.len());
-    for (y, line) in lines.into_iter().enumerate() {
-        for (x, c) in line.chars().enumerate() {
-            map.put(
-                x,
-                y,
-                match c {
-                    '.' => Tile::Open,
-                    '|' => Tile::Wooded,
-                    '#' => Tile::Lumberyard,
-                    _ => bail!("Unexpected tile '{}'", c),
-                },
-            );
-        }
-    }
-    Ok(map)
+    data.grid(|c| {
+        Ok(match c {
+            '.' => Tile::Open,
+            '|' => Tile::Wooded,
+            '#' => Tile::Lumberyard,
+            _ => bail!("Unexpected tile '{}'", c),
+        })
+    })
 }
 
 fn resource_value_after(field: &mut Field, minutes: usize) -> usize {
@@ -79,27 +88,23 @@ fn step(src: &Field, dest: &mut Field) {
     for (x, y) in src.iter_xy() {
         let mut num_wooded = 0;
         let mut num_lumberyard = 0;
-        for yo in 0..3 {
-            for xo in 0..3 {
-                if xo != 1 || yo != 1 {
-                    match src.get((x + xo).wrapping_sub(1), (y + yo).wrapping_sub(1)) {
-                        Tile::Wooded => num_wooded += 1,
-                        Tile::Lumberyard => num_lumberyard += 1,
-                        _ => (),
-                    }
-                }
+        for (x, y) in src.neighbors8(x, y) {
+            match src.get(x, y) {
+                Tile::Wooded => num_wooded += 1,
+                Tile::Lumberyard => num_lumberyard += 1,
+                _ => (),
             }
-            dest.put(
-                x,
-                y,
-                match src.get(x, y) {
-                    Tile::Open if num_wooded >= 3 => Tile::Wooded,
-                    Tile::Wooded if num_lumberyard >= 3 => Tile::Lumberyard,
-                    Tile::Lumberyard if num_wooded == 0 || num_lumberyard == 0 => Tile::Open,
-                    o => o,
-                },
-            )
         }
+        dest.put(
+            x,
+            y,
+            match src.get(x, y) {
+                Tile::Open if num_wooded >= 3 => Tile::Wooded,
+                Tile::Wooded if num_lumberyard >= 3 => Tile::Lumberyard,
+                Tile::Lumberyard if num_wooded == 0 || num_lumberyard == 0 => Tile::Open,
+                o => o,
+            },
+        )
     }
 }
 
@@ -132,20 +137,7 @@ fn print_field(field: &Field) {
 #[cfg(test)]
 #[test]
 fn test() {
-    let data = AocData::from_str(
-        "
-.#.#...|#.
-.....#|##|
-.|..|...#.
-..|#.....#
-#.#|||#|#|
-...#.||...
-.|....|...
-||...#|.#|
-|.||||..|.
-...#.|..|.
-    ",
-    );
+    let data = AocData::from_str(EXAMPLE);
     let mut field = parse_data(&data).unwrap();
     assert_eq!(resource_value_after(&mut field, 10), 1147);
 }
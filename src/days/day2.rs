@@ -34,21 +34,39 @@ fn find_close(ids: &[String]) -> Option<String> {
 
 pub fn run(data: &AocData) -> AocResult {
     let ids: Vec<_> = data.lines()?.collect();
-    let mut count_twice = 0usize;
-    let mut count_thrice = 0usize;
-    for line in &ids {
-        let counts = char_counts(line);
-        if counts.values().any(|&c| c == 2) {
-            count_twice += 1;
-        }
-        if counts.values().any(|&c| c == 3) {
-            count_thrice += 1;
-        }
-    }
-    let second = find_close(&ids).ok_or_else(|| format_err!("No close ids found"))?;
-    answers(count_twice * count_thrice, second)
+    parts(
+        data,
+        || {
+            let mut count_twice = 0usize;
+            let mut count_thrice = 0usize;
+            for line in &ids {
+                let counts = char_counts(line);
+                if counts.values().any(|&c| c == 2) {
+                    count_twice += 1;
+                }
+                if counts.values().any(|&c| c == 3) {
+                    count_thrice += 1;
+                }
+            }
+            Ok(count_twice * count_thrice)
+        },
+        || find_close(&ids).ok_or_else(|| format_err!("No close ids found")),
+    )
 }
 
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: "abcdef\nbababc\nabbcde\nabcccd\naabcdd\nabcdee\nababab",
+        first: Some("12"),
+        second: None,
+    },
+    Example {
+        input: "abcde\nfghij\nklmno\npqrst\nfguij\naxcye\nwvxyz",
+        first: None,
+        second: Some("fgij"),
+    },
+];
+
 #[cfg(test)]
 mod test {
     use super::*;
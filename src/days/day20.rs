@@ -1,4 +1,4 @@
-use crate::array2d::Array2d;
+use crate::array2d::{Array2d, Dir};
 use crate::prelude::*;
 use std::cmp;
 use std::collections::BTreeSet;
@@ -6,12 +6,31 @@ use std::mem;
 
 pub fn run(data: &AocData) -> AocResult {
     let (map, (ox, oy)) = create_map(&data.to_string()?)?;
-    answers(
-        longest_path(&map, ox, oy),
-        count_distant_rooms(&map, ox, oy, 1000),
+    parts(
+        data,
+        || Ok(longest_path(&map, ox, oy)),
+        || Ok(count_distant_rooms(&map, ox, oy, 1000)),
     )
 }
 
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: "^WNE$",
+        first: Some("3"),
+        second: None,
+    },
+    Example {
+        input: "^ENWWW(NEEE|SSE(EE|N))$",
+        first: Some("10"),
+        second: None,
+    },
+    Example {
+        input: "^WSSEESWWWNW(S|NENNEEEENN(ESSSSW(NWSW|SSEN)|WSWWN(E|WWS(E|SS))))$",
+        first: Some("31"),
+        second: None,
+    },
+];
+
 type Group = Vec<Vec<Part>>;
 
 enum Part {
@@ -196,6 +215,16 @@ fn count_distant_rooms(
     count
 }
 
+/// Each cell stores the doors on its top (bit 0) and left (bit 1) side.
+fn has_door(map: &DoorMap, x: usize, y: usize, dir: Dir) -> bool {
+    match dir {
+        Dir::Up => map[(x, y)] & 1 != 0,
+        Dir::Left => map[(x, y)] & 2 != 0,
+        Dir::Down => map.get(x, y + 1) & 1 != 0,
+        Dir::Right => map.get(x + 1, y) & 2 != 0,
+    }
+}
+
 fn pathfind<F>(map: &DoorMap, start_x: usize, start_y: usize, mut f: F)
 where
     F: FnMut(usize),
@@ -209,17 +238,15 @@ where
         for (x, y) in mem::replace(&mut next_positions, BTreeSet::new()).into_iter() {
             visited[(x, y)] = true;
             f(i);
-            if map[(x, y)] & 1 != 0 && !visited.get(x, y.wrapping_sub(1)) {
-                next_positions.insert((x, y - 1));
-            }
-            if map[(x, y)] & 2 != 0 && !visited.get(x.wrapping_sub(1), y) {
-                next_positions.insert((x - 1, y));
-            }
-            if map.get(x, y + 1) & 1 != 0 && !visited.get(x, y + 1) {
-                next_positions.insert((x, y + 1));
-            }
-            if map.get(x + 1, y) & 2 != 0 && !visited.get(x + 1, y) {
-                next_positions.insert((x + 1, y));
+            for &dir in &Dir::ALL {
+                if !has_door(map, x, y, dir) {
+                    continue;
+                }
+                if let Some((x, y)) = dir.step(x, y) {
+                    if !visited.get(x, y) {
+                        next_positions.insert((x, y));
+                    }
+                }
             }
         }
         if next_positions.is_empty() {
@@ -5,13 +5,28 @@ use std::cmp;
 use std::iter;
 use std::u32;
 
-pub fn run(_: &AocData) -> AocResult {
-    let cave = Cave::new(7305, 13, 734);
-    let risk_level = cave.area_risk_level();
-    let mut path_finder = PathFinder::new(cave);
-    answers(risk_level, path_finder.find_path())
+pub fn run(data: &AocData) -> AocResult {
+    let re = Regex::new(r"^depth: (\d+)\s+target: (\d+),(\d+)$").unwrap();
+    let input = data.to_string()?;
+    let captures = re
+        .captures(&input)
+        .ok_or_else(|| format_err!("Failed to parse '{}'", input))?;
+    let depth = captures[1].parse()?;
+    let tx = captures[2].parse()?;
+    let ty = captures[3].parse()?;
+    parts(
+        data,
+        || Ok(Cave::new(depth, tx, ty).area_risk_level()),
+        || Ok(PathFinder::new(Cave::new(depth, tx, ty)).find_path()),
+    )
 }
 
+pub const EXAMPLES: &[Example] = &[Example {
+    input: "depth: 510\ntarget: 10,10",
+    first: Some("114"),
+    second: Some("45"),
+}];
+
 struct Cave {
     tx: usize,
     ty: usize,
@@ -5,10 +5,41 @@ use std::i32;
 
 pub fn run(data: &AocData) -> AocResult {
     let bots = parse_input(data)?;
-    let closest_max = intersect_bots(&bots);
-    answers(num_in_range_of_strongest(&bots), closest_max)
+    parts(
+        data,
+        || Ok(num_in_range_of_strongest(&bots)),
+        || Ok(intersect_bots(&bots)),
+    )
 }
 
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: "
+pos=<0,0,0>, r=4
+pos=<1,0,0>, r=1
+pos=<4,0,0>, r=3
+pos=<0,2,0>, r=1
+pos=<0,5,0>, r=3
+pos=<0,0,3>, r=1
+pos=<1,1,1>, r=1
+pos=<1,1,2>, r=1
+pos=<1,3,1>, r=1",
+        first: Some("7"),
+        second: None,
+    },
+    Example {
+        input: "
+pos=<10,12,12>, r=2
+pos=<12,14,12>, r=2
+pos=<16,12,12>, r=4
+pos=<14,14,14>, r=6
+pos=<50,50,50>, r=200
+pos=<10,10,10>, r=5",
+        first: None,
+        second: Some("36"),
+    },
+];
+
 struct Bot {
     x: i32,
     y: i32,
@@ -27,20 +58,10 @@ impl Bot {
 }
 
 fn parse_input(data: &AocData) -> Result<Vec<Bot>> {
-    let mut bots = Vec::new();
-    let re = Regex::new(r"^pos=<(-?\d+),(-?\d+),(-?\d+)>, r=(\d+)").unwrap();
-    for line in data.lines()? {
-        if let Some(caps) = re.captures(&line) {
-            bots.push(Bot {
-                x: caps[1].parse().unwrap(),
-                y: caps[2].parse().unwrap(),
-                z: caps[3].parse().unwrap(),
-                r: caps[4].parse().unwrap(),
-            });
-        } else {
-            bail!("Failed to parse line: {}", line);
-        }
-    }
+    let bots: Vec<Bot> = data
+        .records(r"^pos=<(-?\d+),(-?\d+),(-?\d+)>, r=(\d+)")?
+        .map(|(x, y, z, r)| Bot { x, y, z, r })
+        .collect();
     if bots.is_empty() {
         bail!("No bots found in input");
     }
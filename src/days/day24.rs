@@ -2,31 +2,52 @@ use crate::prelude::*;
 use std::collections::BTreeSet;
 
 pub fn run(data: &AocData) -> AocResult {
-    let mut game_state = GameState::parse(data)?;
-    let infection_left = {
-        let mut game_state = game_state.clone();
-        game_state.fight_battle();
-        game_state.total_num_units()
-    };
-    let mut min_boost = 0;
-    let mut max_boost = 1;
-    while !test_boost(&game_state, max_boost) {
-        min_boost = max_boost;
-        max_boost *= 2;
-    }
-    while min_boost + 1 < max_boost {
-        let mid_boost = (min_boost + max_boost) / 2;
-        if test_boost(&game_state, mid_boost) {
-            max_boost = mid_boost;
-        } else {
-            min_boost = mid_boost;
-        }
-    }
-    game_state.boost(max_boost);
-    game_state.fight_battle();
-    answers(infection_left, game_state.total_num_units())
+    let game_state = GameState::parse(data)?;
+    parts(
+        data,
+        || {
+            let mut game_state = game_state.clone();
+            game_state.fight_battle();
+            Ok(game_state.total_num_units())
+        },
+        || {
+            let mut min_boost = 0;
+            let mut max_boost = 1;
+            while !test_boost(&game_state, max_boost) {
+                min_boost = max_boost;
+                max_boost *= 2;
+            }
+            while min_boost + 1 < max_boost {
+                let mid_boost = (min_boost + max_boost) / 2;
+                if test_boost(&game_state, mid_boost) {
+                    max_boost = mid_boost;
+                } else {
+                    min_boost = mid_boost;
+                }
+            }
+            let mut game_state = game_state.clone();
+            game_state.boost(max_boost);
+            game_state.fight_battle();
+            Ok(game_state.total_num_units())
+        },
+    )
 }
 
+const EXAMPLE: &str = "
+Immune System:
+17 units each with 5390 hit points (weak to radiation, bludgeoning) with an attack that does 4507 fire damage at initiative 2
+989 units each with 1274 hit points (immune to fire; weak to bludgeoning, slashing) with an attack that does 25 slashing damage at initiative 3
+
+Infection:
+801 units each with 4706 hit points (weak to radiation) with an attack that does 116 bludgeoning damage at initiative 1
+4485 units each with 2961 hit points (immune to radiation; weak to fire, cold) with an attack that does 12 slashing damage at initiative 4";
+
+pub const EXAMPLES: &[Example] = &[Example {
+    input: EXAMPLE,
+    first: Some("5216"),
+    second: Some("51"),
+}];
+
 fn test_boost(game_state: &GameState, boost: u32) -> bool {
     let mut game_state = game_state.clone();
     game_state.boost(boost);
@@ -62,44 +83,43 @@ impl GameState {
         let re = Regex::new(r"(\d+) units each with (\d+) hit points (\([^\)]*\))? ?with an attack that does (\d+) (\w+) damage at initiative (\d+)").unwrap();
         let attr_re = Regex::new(r"(weak|immune) to ([^\);]+)").unwrap();
 
-        let mut is_immune_system = true;
-        let mut immune_system = Vec::new();
-        let mut infection = Vec::new();
-
-        for line in data.lines()? {
-            if line == "Immune System:" {
-                is_immune_system = true;
-            } else if line == "Infection:" {
-                is_immune_system = false;
-            } else if let Some(caps) = re.captures(&line) {
-                let mut weakness = BTreeSet::new();
-                let mut immunity = BTreeSet::new();
-                if let Some(attrs) = caps.get(3) {
-                    for caps in attr_re.captures_iter(attrs.as_str()) {
-                        let iter = caps[2].split(",").map(|t| t.trim().to_string());
-                        if &caps[1] == "weak" {
-                            weakness.extend(iter);
-                        } else {
-                            immunity.extend(iter);
-                        }
+        let parse_group = |line: &str| -> Result<Group> {
+            let caps = re
+                .captures(line)
+                .ok_or_else(|| format_err!("Failed to parse line: {}", line))?;
+            let mut weakness = BTreeSet::new();
+            let mut immunity = BTreeSet::new();
+            if let Some(attrs) = caps.get(3) {
+                for caps in attr_re.captures_iter(attrs.as_str()) {
+                    let iter = caps[2].split(",").map(|t| t.trim().to_string());
+                    if &caps[1] == "weak" {
+                        weakness.extend(iter);
+                    } else {
+                        immunity.extend(iter);
                     }
                 }
-                let group = Group {
-                    num_units: caps[1].parse().unwrap(),
-                    hp: caps[2].parse().unwrap(),
-                    weakness,
-                    immunity,
-                    dp: caps[4].parse().unwrap(),
-                    damage_type: caps[5].into(),
-                    initiative: caps[6].parse().unwrap(),
-                };
-                if is_immune_system {
-                    immune_system.push(group);
-                } else {
-                    infection.push(group);
-                }
-            } else if !line.is_empty() {
-                bail!("Failed to parse line: {}", line);
+            }
+            Ok(Group {
+                num_units: caps[1].parse().unwrap(),
+                hp: caps[2].parse().unwrap(),
+                weakness,
+                immunity,
+                dp: caps[4].parse().unwrap(),
+                damage_type: caps[5].into(),
+                initiative: caps[6].parse().unwrap(),
+            })
+        };
+
+        let mut immune_system = Vec::new();
+        let mut infection = Vec::new();
+        for section in data.sections()? {
+            let army = match section[0].as_str() {
+                "Immune System:" => &mut immune_system,
+                "Infection:" => &mut infection,
+                header => bail!("Unknown army: {}", header),
+            };
+            for line in &section[1..] {
+                army.push(parse_group(line)?);
             }
         }
 
@@ -245,15 +265,7 @@ fn attack(attacker: &Group, defender: &mut Group) {
 #[cfg(test)]
 #[test]
 fn test() {
-    let data = AocData::from_str("
-Immune System:
-17 units each with 5390 hit points (weak to radiation, bludgeoning) with an attack that does 4507 fire damage at initiative 2
-989 units each with 1274 hit points (immune to fire; weak to bludgeoning, slashing) with an attack that does 25 slashing damage at initiative 3
-
-Infection:
-801 units each with 4706 hit points (weak to radiation) with an attack that does 116 bludgeoning damage at initiative 1
-4485 units each with 2961 hit points (immune to radiation; weak to fire, cold) with an attack that does 12 slashing damage at initiative 4
-    ");
+    let data = AocData::from_str(EXAMPLE);
 
     let mut game_state = GameState::parse(&data).unwrap();
     while !game_state.is_battle_over() {
@@ -2,16 +2,30 @@ use crate::prelude::*;
 use std::str::FromStr;
 
 pub fn run(data: &AocData) -> AocResult {
+    // The last day has only one part.
     let constellations = read_constellations(data)?;
     answer(constellations.len())
 }
 
+pub const EXAMPLES: &[Example] = &[Example {
+    input: "
+0,0,0,0
+3,0,0,0
+0,3,0,0
+0,0,3,0
+0,0,0,3
+0,0,0,6
+9,0,0,0
+12,0,0,0",
+    first: Some("2"),
+    second: None,
+}];
+
 fn read_constellations(data: &AocData) -> Result<Vec<Vec<Vector4>>> {
     let mut constellations: Vec<Vec<Vector4>> = Vec::new();
     let mut groups: Vec<usize> = Vec::new();
 
-    for line in data.lines()? {
-        let v: Vector4 = line.parse()?;
+    for v in data.values::<Vector4>()? {
         for (index, constellation) in constellations.iter_mut().enumerate() {
             if constellation.iter().any(|c| v.dist(c) <= 3) {
                 if groups.is_empty() {
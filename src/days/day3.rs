@@ -10,20 +10,16 @@ struct Claim {
 }
 
 pub fn run(data: &AocData) -> AocResult {
-    let re = Regex::new(r"^#(\d+) @ (\d+),(\d+): (\d+)x(\d+)$").unwrap();
-    let mut claims = Vec::new();
-    for line in data.lines()? {
-        let captures = re
-            .captures(&line)
-            .ok_or_else(|| format_err!("Failed to parse line '{}'", line))?;
-        claims.push(Claim {
-            id: captures[1].parse().unwrap(),
-            x: captures[2].parse().unwrap(),
-            y: captures[3].parse().unwrap(),
-            width: captures[4].parse().unwrap(),
-            height: captures[5].parse().unwrap(),
-        });
-    }
+    let claims: Vec<Claim> = data
+        .records(r"^#(\d+) @ (\d+),(\d+): (\d+)x(\d+)$")?
+        .map(|(id, x, y, width, height)| Claim {
+            id,
+            x,
+            y,
+            width,
+            height,
+        })
+        .collect();
 
     let mut field: Vec<Vec<u8>> = Vec::new();
     for claim in &claims {
@@ -40,20 +36,31 @@ pub fn run(data: &AocData) -> AocResult {
         }
     }
 
-    let overlapping_squares: usize = field
-        .iter()
-        .map(|row| row.iter().filter(|&&c| c > 1).count())
-        .sum();
-
-    let nonoverlaping_claim = claims
-        .into_iter()
-        .find(|claim| {
-            (claim.y..(claim.y + claim.height)).all(|y| {
-                let row = &field[y as usize];
-                (claim.x..(claim.x + claim.width)).all(|x| row[x as usize] == 1)
-            })
-        })
-        .ok_or_else(|| format_err!("Failed to find non-overlapping claim"))?;
-
-    answers(overlapping_squares, nonoverlaping_claim.id)
+    parts(
+        data,
+        || {
+            Ok(field
+                .iter()
+                .map(|row| row.iter().filter(|&&c| c > 1).count())
+                .sum::<usize>())
+        },
+        || {
+            let nonoverlaping_claim = claims
+                .iter()
+                .find(|claim| {
+                    (claim.y..(claim.y + claim.height)).all(|y| {
+                        let row = &field[y as usize];
+                        (claim.x..(claim.x + claim.width)).all(|x| row[x as usize] == 1)
+                    })
+                })
+                .ok_or_else(|| format_err!("Failed to find non-overlapping claim"))?;
+            Ok(nonoverlaping_claim.id)
+        },
+    )
 }
+
+pub const EXAMPLES: &[Example] = &[Example {
+    input: "#1 @ 1,3: 4x4\n#2 @ 3,1: 4x4\n#3 @ 5,5: 2x2",
+    first: Some("4"),
+    second: Some("3"),
+}];
@@ -4,6 +4,31 @@ use std::ops::Range;
 
 type Guards = HashMap<u32, Vec<Range<u32>>>;
 
+const EXAMPLE: &str = "
+[1518-11-01 00:00] Guard #10 begins shift
+[1518-11-01 00:05] falls asleep
+[1518-11-01 00:25] wakes up
+[1518-11-01 00:30] falls asleep
+[1518-11-01 00:55] wakes up
+[1518-11-01 23:58] Guard #99 begins shift
+[1518-11-02 00:40] falls asleep
+[1518-11-02 00:50] wakes up
+[1518-11-03 00:05] Guard #10 begins shift
+[1518-11-03 00:24] falls asleep
+[1518-11-03 00:29] wakes up
+[1518-11-04 00:02] Guard #99 begins shift
+[1518-11-04 00:36] falls asleep
+[1518-11-04 00:46] wakes up
+[1518-11-05 00:03] Guard #99 begins shift
+[1518-11-05 00:45] falls asleep
+[1518-11-05 00:55] wakes up";
+
+pub const EXAMPLES: &[Example] = &[Example {
+    input: EXAMPLE,
+    first: Some("240"),
+    second: Some("4455"),
+}];
+
 fn parse_records<T: AsRef<str>>(records: &[T]) -> Result<Guards> {
     let mut guards: HashMap<u32, Vec<Range<u32>>> = HashMap::new();
     let re = Regex::new(r"\d\d:(\d\d)] (Guard #(\d+) begins shift|.+)$").unwrap();
@@ -97,14 +122,17 @@ pub fn run(data: &AocData) -> AocResult {
     records.sort();
     let guards = parse_records(&records)?;
 
-    let most_sleepy_guard = find_most_sleepy_guard(&guards);
-    let (most_sleepy_minute, _) = find_most_sleepy_minute(&guards[&most_sleepy_guard]);
-
-    let (best_guard, best_minute) = find_most_likely_asleep(&guards);
-
-    answers(
-        most_sleepy_guard * most_sleepy_minute,
-        best_guard * best_minute,
+    parts(
+        data,
+        || {
+            let most_sleepy_guard = find_most_sleepy_guard(&guards);
+            let (most_sleepy_minute, _) = find_most_sleepy_minute(&guards[&most_sleepy_guard]);
+            Ok(most_sleepy_guard * most_sleepy_minute)
+        },
+        || {
+            let (best_guard, best_minute) = find_most_likely_asleep(&guards);
+            Ok(best_guard * best_minute)
+        },
     )
 }
 
@@ -114,25 +142,7 @@ mod test {
 
     #[test]
     fn sleepy_guards() {
-        let data = [
-            "[1518-11-01 00:00] Guard #10 begins shift",
-            "[1518-11-01 00:05] falls asleep",
-            "[1518-11-01 00:25] wakes up",
-            "[1518-11-01 00:30] falls asleep",
-            "[1518-11-01 00:55] wakes up",
-            "[1518-11-01 23:58] Guard #99 begins shift",
-            "[1518-11-02 00:40] falls asleep",
-            "[1518-11-02 00:50] wakes up",
-            "[1518-11-03 00:05] Guard #10 begins shift",
-            "[1518-11-03 00:24] falls asleep",
-            "[1518-11-03 00:29] wakes up",
-            "[1518-11-04 00:02] Guard #99 begins shift",
-            "[1518-11-04 00:36] falls asleep",
-            "[1518-11-04 00:46] wakes up",
-            "[1518-11-05 00:03] Guard #99 begins shift",
-            "[1518-11-05 00:45] falls asleep",
-            "[1518-11-05 00:55] wakes up",
-        ];
+        let data: Vec<&str> = EXAMPLE.trim().lines().collect();
 
         let guards = parse_records(&data).unwrap();
         let most_sleepy_guard = find_most_sleepy_guard(&guards);
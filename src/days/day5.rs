@@ -23,27 +23,38 @@ fn reduce_polymer(p: &mut Vec<u8>) {
 pub fn run(data: &AocData) -> AocResult {
     let polymer = polymer_from_str(&data.to_string()?);
 
-    let mut poly_part1 = polymer.clone();
-    reduce_polymer(&mut poly_part1);
-
-    let unit_types: HashSet<u8> = polymer.iter().map(|v| v | 32).collect();
-    let best_length = unit_types
-        .into_iter()
-        .map(|unit_type| {
-            let mut poly: Vec<u8> = polymer
-                .iter()
-                .cloned()
-                .filter(|&c| c | 32 != unit_type)
-                .collect();
+    parts(
+        data,
+        || {
+            let mut poly = polymer.clone();
             reduce_polymer(&mut poly);
-            poly.len()
-        })
-        .min()
-        .ok_or_else(|| format_err!("No unit types found in polymer"))?;
-
-    answers(poly_part1.len(), best_length)
+            Ok(poly.len())
+        },
+        || {
+            let unit_types: HashSet<u8> = polymer.iter().map(|v| v | 32).collect();
+            unit_types
+                .into_iter()
+                .map(|unit_type| {
+                    let mut poly: Vec<u8> = polymer
+                        .iter()
+                        .cloned()
+                        .filter(|&c| c | 32 != unit_type)
+                        .collect();
+                    reduce_polymer(&mut poly);
+                    poly.len()
+                })
+                .min()
+                .ok_or_else(|| format_err!("No unit types found in polymer"))
+        },
+    )
 }
 
+pub const EXAMPLES: &[Example] = &[Example {
+    input: "dabAcCaCBAcCcaDA",
+    first: Some("10"),
+    second: Some("4"),
+}];
+
 #[cfg(test)]
 mod test {
     use super::*;
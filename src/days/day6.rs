@@ -265,14 +265,33 @@ fn find_inside_area(coords: &Coords, max_distance: usize) -> Result<usize> {
     Ok(sum)
 }
 
+const EXAMPLE: &str = "
+1, 1
+1, 6
+8, 3
+3, 4
+5, 5
+8, 9";
+
+// The second part of the example uses a smaller distance limit than the
+// puzzle, so only the first answer can be checked.
+pub const EXAMPLES: &[Example] = &[Example {
+    input: EXAMPLE,
+    first: Some("17"),
+    second: None,
+}];
+
 pub fn run(data: &AocData) -> AocResult {
     let coords = parse_coords(&mut data.lines()?)?;
-    let grid = fill_grid(&coords);
-    let areas = count_areas(&grid);
 
-    answers(
-        areas.into_iter().max().unwrap(),
-        find_inside_area(&coords, 10000)?,
+    parts(
+        data,
+        || {
+            let grid = fill_grid(&coords);
+            let areas = count_areas(&grid);
+            Ok(areas.into_iter().max().unwrap())
+        },
+        || find_inside_area(&coords, 10000),
     )
 }
 
@@ -282,8 +301,8 @@ mod test {
 
     #[test]
     fn example() {
-        let data = &["1, 1", "1, 6", "8, 3", "3, 4", "5, 5", "8, 9"];
-        let coords = parse_coords(&mut data.iter().map(|s| s.to_string())).unwrap();
+        let data = AocData::from_str(EXAMPLE);
+        let coords = parse_coords(&mut data.lines().unwrap()).unwrap();
         let grid = fill_grid(&coords);
         let areas = count_areas(&grid);
         assert_eq!(areas.into_iter().max().unwrap(), 17);
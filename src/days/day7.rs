@@ -70,13 +70,30 @@ fn simulate(steps: u32, deps: &[u32; 26], num_workers: usize, base_time: usize)
     clock
 }
 
-pub fn run(data: &AocData) -> AocResult {
-    let (steps, deps) = parse_dependencies(&mut data.lines()?)?;
-    let order = compute_order(steps, &deps)?;
+const EXAMPLE: &str = "
+Step C must be finished before step A can begin.
+Step C must be finished before step F can begin.
+Step A must be finished before step B can begin.
+Step A must be finished before step D can begin.
+Step B must be finished before step E can begin.
+Step D must be finished before step E can begin.
+Step F must be finished before step E can begin.";
 
-    let time = simulate(steps, &deps, 5, 60);
+// The second part of the example uses fewer workers and shorter steps than
+// the puzzle, so only the first answer can be checked.
+pub const EXAMPLES: &[Example] = &[Example {
+    input: EXAMPLE,
+    first: Some("CABDFE"),
+    second: None,
+}];
 
-    answers(order, time)
+pub fn run(data: &AocData) -> AocResult {
+    let (steps, deps) = parse_dependencies(&mut data.lines()?)?;
+    parts(
+        data,
+        || compute_order(steps, &deps),
+        || Ok(simulate(steps, &deps, 5, 60)),
+    )
 }
 
 #[cfg(test)]
@@ -85,20 +102,8 @@ mod test {
 
     #[test]
     fn test() {
-        let (steps, deps) = parse_dependencies(
-            &mut [
-                "Step C must be finished before step A can begin.",
-                "Step C must be finished before step F can begin.",
-                "Step A must be finished before step B can begin.",
-                "Step A must be finished before step D can begin.",
-                "Step B must be finished before step E can begin.",
-                "Step D must be finished before step E can begin.",
-                "Step F must be finished before step E can begin.",
-            ]
-            .into_iter()
-            .map(|s| s.to_string()),
-        )
-        .unwrap();
+        let data = AocData::from_str(EXAMPLE);
+        let (steps, deps) = parse_dependencies(&mut data.lines().unwrap()).unwrap();
         let time = simulate(steps, &deps, 2, 0);
         assert_eq!(time, 15);
     }
@@ -70,9 +70,15 @@ impl Node {
 
 pub fn run(data: &AocData) -> AocResult {
     let tree = Node::from_string(data.to_string()?)?;
-    answers(tree.sum_metadata(), tree.value())
+    parts(data, || Ok(tree.sum_metadata()), || Ok(tree.value()))
 }
 
+pub const EXAMPLES: &[Example] = &[Example {
+    input: "2 3 0 3 10 11 12 1 1 0 1 99 2 1 1 2",
+    first: Some("138"),
+    second: Some("66"),
+}];
+
 #[cfg(test)]
 #[test]
 fn test() {
@@ -1,10 +1,37 @@
 use crate::prelude::*;
 use std::ops::Index;
 
-pub fn run(_: &AocData) -> AocResult {
-    answers(marble_score(452, 71250), marble_score(452, 7125000))
+pub fn run(data: &AocData) -> AocResult {
+    let re = Regex::new(r"^(\d+) players; last marble is worth (\d+) points$").unwrap();
+    let input = data.to_string()?;
+    let captures = re
+        .captures(&input)
+        .ok_or_else(|| format_err!("Failed to parse '{}'", input))?;
+    let num_players: usize = captures[1].parse()?;
+    let last_marble: usize = captures[2].parse()?;
+    if num_players == 0 {
+        bail!("Need at least one player");
+    }
+    parts(
+        data,
+        || Ok(marble_score(num_players, last_marble)),
+        || Ok(marble_score(num_players, last_marble * 100)),
+    )
 }
 
+pub const EXAMPLES: &[Example] = &[
+    Example {
+        input: "9 players; last marble is worth 25 points",
+        first: Some("32"),
+        second: None,
+    },
+    Example {
+        input: "10 players; last marble is worth 1618 points",
+        first: Some("8317"),
+        second: None,
+    },
+];
+
 struct Circle<T>(Vec<(usize, usize, T)>);
 
 struct Cursor(usize);
@@ -0,0 +1,464 @@
+use crate::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(test)]
+mod asm;
+mod compile;
+mod debugger;
+mod disasm;
+#[cfg(test)]
+mod equiv;
+mod infer;
+mod optimize;
+mod profile;
+mod trace;
+mod word;
+
+pub use self::compile::Compiled;
+pub use self::debugger::debug;
+pub use self::disasm::{disassemble, to_dot};
+#[cfg(test)]
+pub use self::equiv::Equivalence;
+pub use self::infer::{infer_opcodes, matching_ops, Encoded, OpcodeMap, Sample};
+pub use self::profile::profile;
+pub use self::trace::replay;
+pub use self::word::{Overflow, Word};
+
+use self::optimize::Fused;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum Op {
+    AddR,
+    AddI,
+    MulR,
+    MulI,
+    BanR,
+    BanI,
+    BorR,
+    BorI,
+    SetR,
+    SetI,
+    GtIR,
+    GtRI,
+    GtRR,
+    EqIR,
+    EqRI,
+    EqRR,
+}
+
+impl Op {
+    pub const ALL: [Op; 16] = [
+        Op::AddR,
+        Op::AddI,
+        Op::MulR,
+        Op::MulI,
+        Op::BanR,
+        Op::BanI,
+        Op::BorR,
+        Op::BorI,
+        Op::SetR,
+        Op::SetI,
+        Op::GtIR,
+        Op::GtRI,
+        Op::GtRR,
+        Op::EqIR,
+        Op::EqRI,
+        Op::EqRR,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Op::AddR => "addr",
+            Op::AddI => "addi",
+            Op::MulR => "mulr",
+            Op::MulI => "muli",
+            Op::BanR => "banr",
+            Op::BanI => "bani",
+            Op::BorR => "borr",
+            Op::BorI => "bori",
+            Op::SetR => "setr",
+            Op::SetI => "seti",
+            Op::GtIR => "gtir",
+            Op::GtRI => "gtri",
+            Op::GtRR => "gtrr",
+            Op::EqIR => "eqir",
+            Op::EqRI => "eqri",
+            Op::EqRR => "eqrr",
+        }
+    }
+
+    /// Whether operand `a` names a register rather than a value.
+    pub fn a_is_reg(self) -> bool {
+        !matches!(self, Op::SetI | Op::GtIR | Op::EqIR)
+    }
+
+    /// Whether operand `b` names a register. The set instructions ignore it.
+    pub fn b_is_reg(self) -> bool {
+        matches!(
+            self,
+            Op::AddR | Op::MulR | Op::BanR | Op::BorR | Op::GtIR | Op::GtRR | Op::EqIR | Op::EqRR
+        )
+    }
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Op {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Op> {
+        match Op::ALL.iter().find(|op| op.name() == s) {
+            Some(&op) => Ok(op),
+            None => bail!("Unknown instruction '{}'", s),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Inst {
+    pub op: Op,
+    pub a: usize,
+    pub b: usize,
+    pub c: usize,
+}
+
+impl Inst {
+    pub fn new(op: Op, a: usize, b: usize, c: usize) -> Inst {
+        Inst { op, a, b, c }
+    }
+
+    /// Checks that all register operands fit a machine with `num_regs`
+    /// registers.
+    pub fn check(&self, num_regs: usize) -> Result<()> {
+        let regs = [
+            (self.op.a_is_reg(), self.a),
+            (self.op.b_is_reg(), self.b),
+            (true, self.c),
+        ];
+        for &(is_reg, r) in &regs {
+            if is_reg && r >= num_regs {
+                bail!("Register out of range in '{}': {}", self, r);
+            }
+        }
+        Ok(())
+    }
+
+    /// Computes the value the instruction writes to register `c`, or `None`
+    /// if it overflows in `Overflow::Checked` mode. `reg` must be large
+    /// enough for the register operands.
+    pub fn eval<W: Word>(&self, reg: &[W], overflow: Overflow) -> Option<W> {
+        let (a, b) = (self.a, self.b);
+        let imm = |x| W::from_usize(x, overflow);
+        let flag = |cond| if cond { W::ONE } else { W::ZERO };
+        Some(match self.op {
+            Op::AddR => reg[a].add(reg[b], overflow)?,
+            Op::AddI => reg[a].add(imm(b)?, overflow)?,
+            Op::MulR => reg[a].mul(reg[b], overflow)?,
+            Op::MulI => reg[a].mul(imm(b)?, overflow)?,
+            Op::BanR => reg[a] & reg[b],
+            Op::BanI => reg[a] & imm(b)?,
+            Op::BorR => reg[a] | reg[b],
+            Op::BorI => reg[a] | imm(b)?,
+            Op::SetR => reg[a],
+            Op::SetI => imm(a)?,
+            Op::GtIR => flag(imm(a)? > reg[b]),
+            Op::GtRI => flag(reg[a] > imm(b)?),
+            Op::GtRR => flag(reg[a] > reg[b]),
+            Op::EqIR => flag(imm(a)? == reg[b]),
+            Op::EqRI => flag(reg[a] == imm(b)?),
+            Op::EqRR => flag(reg[a] == reg[b]),
+        })
+    }
+
+    /// Executes the instruction on `reg` with wrapping arithmetic. `reg`
+    /// must be large enough for the register operands.
+    pub fn exec(&self, reg: &mut [usize]) {
+        reg[self.c] = self.eval(reg, Overflow::Wrapping).unwrap();
+    }
+}
+
+impl fmt::Display for Inst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.op, self.a, self.b, self.c)
+    }
+}
+
+impl FromStr for Inst {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Inst> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        if words.len() != 4 {
+            bail!("Expected an instruction and three operands");
+        }
+        Ok(Inst {
+            op: words[0].parse()?,
+            a: words[1].parse()?,
+            b: words[2].parse()?,
+            c: words[3].parse()?,
+        })
+    }
+}
+
+/// A program in the puzzle's text format: an optional `#ip <reg>` line
+/// binding the instruction pointer to a register, then one instruction per
+/// line.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Program {
+    pub ip: Option<usize>,
+    pub code: Vec<Inst>,
+}
+
+enum Line {
+    Ip(usize),
+    Inst(Inst),
+}
+
+impl FromStr for Line {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Line> {
+        if let Some(reg) = s.strip_prefix("#ip ") {
+            Ok(Line::Ip(reg.trim().parse()?))
+        } else {
+            Ok(Line::Inst(s.parse()?))
+        }
+    }
+}
+
+impl Program {
+    pub fn parse(data: &AocData) -> Result<Program> {
+        let program = Program::from_lines(data.values::<Line>()?)?;
+        // Running what is left of a program with bad lines is pointless.
+        if !data.errors().is_empty() {
+            bail!("Invalid program");
+        }
+        Ok(program)
+    }
+
+    /// Parses the program in `data`, reporting input errors in preference
+    /// to the program being invalid.
+    pub fn load(data: AocData) -> Result<Program> {
+        let program = Program::parse(&data);
+        data.ok()?;
+        program
+    }
+
+    fn from_lines<I: Iterator<Item = Line>>(lines: I) -> Result<Program> {
+        let mut program = Program::default();
+        for line in lines {
+            match line {
+                Line::Ip(_) if !program.code.is_empty() => {
+                    bail!("#ip must come before the first instruction")
+                }
+                Line::Ip(reg) => program.ip = Some(reg),
+                Line::Inst(inst) => program.code.push(inst),
+            }
+        }
+        Ok(program)
+    }
+}
+
+impl FromStr for Program {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Program> {
+        let lines = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .enumerate()
+            .map(|(i, line)| line.parse().map_err(|e| format_err!("{}: {}", i + 1, e)))
+            .collect::<Result<Vec<Line>>>()?;
+        Program::from_lines(lines.into_iter())
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(ip) = self.ip {
+            writeln!(f, "#ip {}", ip)?;
+        }
+        for inst in &self.code {
+            writeln!(f, "{}", inst)?;
+        }
+        Ok(())
+    }
+}
+
+/// A machine whose registers hold words of type `W`.
+#[derive(Clone, Debug)]
+pub struct Vm<W: Word> {
+    pub reg: Vec<W>,
+    pub pc: usize,
+    pub ip: Option<usize>,
+    pub code: Vec<Inst>,
+    pub overflow: Overflow,
+    /// The address of the instruction which overflowed in checked mode.
+    pub overflowed_at: Option<usize>,
+    /// Super-instructions replacing loops, indexed by address.
+    fused: Vec<Option<Fused>>,
+}
+
+/// The machine of the puzzles, with registers as wide as a pointer.
+pub type Machine = Vm<usize>;
+
+impl<W: Word> Vm<W> {
+    /// Creates a machine with `num_regs` registers, all zero, checking that
+    /// the program only uses those. Arithmetic wraps around.
+    pub fn new(program: &Program, num_regs: usize) -> Result<Vm<W>> {
+        if let Some(ip) = program.ip {
+            if ip >= num_regs {
+                bail!("Instruction pointer bound to missing register {}", ip);
+            }
+        }
+        for inst in &program.code {
+            inst.check(num_regs)?;
+        }
+        Ok(Vm {
+            reg: vec![W::ZERO; num_regs],
+            pc: 0,
+            ip: program.ip,
+            code: program.code.clone(),
+            overflow: Overflow::Wrapping,
+            overflowed_at: None,
+            fused: vec![],
+        })
+    }
+
+    /// Replaces the loops the optimiser recognises by super-instructions,
+    /// returning how many it found. These compute in wrapping `usize`
+    /// arithmetic, so other machines are left as they are.
+    #[cfg(test)]
+    pub fn optimize(&mut self) -> usize {
+        if std::mem::size_of::<W>() != std::mem::size_of::<usize>()
+            || self.overflow != Overflow::Wrapping
+        {
+            return 0;
+        }
+        let idioms = optimize::find_idioms(&self.code, self.ip);
+        self.fused = vec![None; self.code.len()];
+        for (pc, fused) in &idioms {
+            self.fused[*pc] = Some(fused.clone());
+        }
+        idioms.len()
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.pc >= self.code.len()
+    }
+
+    fn exec_fused(&mut self, fused: &Fused, ip: usize) -> Option<usize> {
+        let mut reg = self
+            .reg
+            .iter()
+            .map(|r| r.to_usize())
+            .collect::<Option<Vec<usize>>>()?;
+        let pc = fused.exec(&mut reg, ip);
+        for (r, value) in self.reg.iter_mut().zip(reg) {
+            *r = W::from_usize(value, Overflow::Wrapping)?;
+        }
+        Some(pc)
+    }
+
+    /// Executes the instruction at `pc`, returning the address of the next
+    /// one or `None` on overflow.
+    fn exec(&mut self, pc: usize) -> Option<usize> {
+        let inst = self.code[pc];
+        if let (Some(Some(fused)), Some(ip)) = (self.fused.get(pc), self.ip) {
+            return self.exec_fused(&fused.clone(), ip);
+        }
+        match self.ip {
+            Some(ip) => {
+                self.reg[ip] = W::from_usize(pc, self.overflow)?;
+                self.reg[inst.c] = inst.eval(&self.reg, self.overflow)?;
+                self.reg[ip].to_usize()?.checked_add(1)
+            }
+            None => {
+                self.reg[inst.c] = inst.eval(&self.reg, self.overflow)?;
+                Some(pc + 1)
+            }
+        }
+    }
+
+    /// Executes one instruction, returning false once the program has
+    /// halted or overflowed.
+    pub fn step(&mut self) -> bool {
+        if self.is_halted() || self.overflowed_at.is_some() {
+            return false;
+        }
+        match self.exec(self.pc) {
+            Some(pc) => self.pc = pc,
+            None => self.overflowed_at = Some(self.pc),
+        }
+        true
+    }
+
+    pub fn run(&mut self) {
+        while self.step() {}
+    }
+
+    /// Runs until the program halts, failing if it overflowed.
+    pub fn try_run(&mut self) -> Result<()> {
+        self.run();
+        match self.overflowed_at {
+            Some(pc) => bail!("Overflow in '{}' at {:02}", self.code[pc], pc),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn example() {
+        let text = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5
+";
+        let program: Program = text.parse().unwrap();
+        assert_eq!(program.to_string(), text);
+        let mut machine = Machine::new(&program, 6).unwrap();
+        machine.run();
+        assert_eq!(machine.reg, [6, 5, 6, 0, 0, 9]);
+        assert!(Machine::new(&program, 4).is_err());
+    }
+
+    #[test]
+    fn overflow() {
+        // Doubles r1 until it wraps around to 0.
+        let program: Program = "#ip 0
+seti 1 0 1
+muli 1 2 1
+eqri 1 0 3
+addr 0 3 0
+seti 0 0 0"
+            .parse()
+            .unwrap();
+        let mut vm: Vm<u8> = Vm::new(&program, 4).unwrap();
+        vm.try_run().unwrap();
+        assert_eq!(vm.reg, [4, 0, 0, 1]);
+
+        let mut vm: Vm<u16> = Vm::new(&program, 4).unwrap();
+        vm.overflow = Overflow::Checked;
+        let err = vm.try_run().unwrap_err();
+        assert_eq!(err.to_string(), "Overflow in 'muli 1 2 1' at 01");
+        assert_eq!(vm.reg[1], 32768);
+
+        let mut vm: Vm<u8> = Vm::new(&program, 4).unwrap();
+        vm.overflow = Overflow::Saturating;
+        for _ in 0..100 {
+            vm.step();
+        }
+        assert!(!vm.is_halted());
+        assert_eq!(vm.reg[1], 255);
+    }
+}
@@ -0,0 +1,298 @@
+use super::{Inst, Op, Program};
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// One line of assembly after the labels and comments are stripped.
+enum Stmt<'a> {
+    Inst(Op, [&'a str; 3]),
+    /// `jmp <label>`, lowered to a write of the instruction pointer.
+    Jmp(&'a str),
+    /// `jz <reg> <label>`, lowered to a comparison and a relative jump.
+    Jz(&'a str, &'a str),
+}
+
+impl<'a> Stmt<'a> {
+    fn len(&self) -> usize {
+        match self {
+            Stmt::Inst(..) | Stmt::Jmp(_) => 1,
+            Stmt::Jz(..) => 4,
+        }
+    }
+}
+
+/// The names defined by a program: labels, register aliases and the
+/// registers bound to the instruction pointer and used for scratch.
+#[derive(Default)]
+struct Symbols<'a> {
+    labels: HashMap<&'a str, usize>,
+    aliases: HashMap<&'a str, usize>,
+    ip: Option<usize>,
+    scratch: Option<usize>,
+}
+
+impl<'a> Symbols<'a> {
+    fn register(&self, word: &str) -> Result<usize> {
+        if word == "ip" {
+            return self
+                .ip
+                .ok_or_else(|| format_err!("'ip' used without an #ip line"));
+        }
+        if let Some(&reg) = self.aliases.get(word) {
+            return Ok(reg);
+        }
+        word.trim_start_matches('r')
+            .parse()
+            .map_err(|_| format_err!("Unknown register '{}'", word))
+    }
+
+    fn value(&self, word: &str) -> Result<usize> {
+        if let Some(&address) = self.labels.get(word) {
+            return Ok(address);
+        }
+        word.parse()
+            .map_err(|_| format_err!("Unknown label or value '{}'", word))
+    }
+
+    /// The value to write to the instruction pointer to continue at `label`.
+    fn jump(&self, label: &str) -> Result<usize> {
+        match self.labels.get(label) {
+            Some(0) => bail!("Can't jump to '{}' at address 0", label),
+            Some(&address) => Ok(address - 1),
+            None => bail!("Unknown label '{}'", label),
+        }
+    }
+
+    fn lower(&self, stmt: &Stmt, pc: usize, code: &mut Vec<Inst>) -> Result<()> {
+        let ip = || self.ip.ok_or_else(|| format_err!("Jumps need an #ip line"));
+        match *stmt {
+            Stmt::Inst(op, [a, b, c]) => {
+                let a = if op.a_is_reg() {
+                    self.register(a)?
+                } else {
+                    self.value(a)?
+                };
+                let b = if op.b_is_reg() {
+                    self.register(b)?
+                } else {
+                    self.value(b)?
+                };
+                code.push(Inst::new(op, a, b, self.register(c)?));
+            }
+            Stmt::Jmp(label) => code.push(Inst::new(Op::SetI, self.jump(label)?, 0, ip()?)),
+            Stmt::Jz(reg, label) => {
+                let (ip, reg) = (ip()?, self.register(reg)?);
+                let tmp = self.scratch.ok_or_else(|| {
+                    format_err!("jz needs a scratch register, declare one with 'scratch <reg>'")
+                })?;
+                if tmp == reg || tmp == ip {
+                    bail!("The scratch register can't be the tested one or ip");
+                }
+                // Unless the register is zero, skip over the jump to the label.
+                code.push(Inst::new(Op::EqRI, reg, 0, tmp));
+                code.push(Inst::new(Op::AddR, tmp, ip, ip));
+                code.push(Inst::new(Op::SetI, pc + 3, 0, ip));
+                code.push(Inst::new(Op::SetI, self.jump(label)?, 0, ip));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits a line into the labels in front of it, each ending in a colon, and
+/// the words after them, dropping the comment.
+fn split_line(line: &str) -> (Vec<&str>, Vec<&str>) {
+    let line = line.split(';').next().unwrap().trim();
+    let (labels, rest) = split_labels(line);
+    (labels, rest.split_whitespace().collect())
+}
+
+/// Finds the register bound to the instruction pointer, so that `ip` can be
+/// used and aliased before the `#ip` line.
+fn find_ip(source: &str) -> Result<Option<usize>> {
+    let mut ip = None;
+    for (i, line) in source.lines().enumerate() {
+        if let ["#ip", reg] = split_line(line).1[..] {
+            if ip.is_some() {
+                bail!("{}: More than one #ip line", i + 1);
+            }
+            let reg = Symbols::default()
+                .register(reg)
+                .map_err(|e| format_err!("{}: {}", i + 1, e))?;
+            ip = Some(reg);
+        }
+    }
+    Ok(ip)
+}
+
+/// Splits off the labels in front of a line, each ending in a colon.
+fn split_labels(mut line: &str) -> (Vec<&str>, &str) {
+    let mut labels = vec![];
+    while let Some(colon) = line.find(':') {
+        let label = line[..colon].trim();
+        if label.is_empty() || label.contains(char::is_whitespace) {
+            break;
+        }
+        labels.push(label);
+        line = line[colon + 1..].trim_start();
+    }
+    (labels, line)
+}
+
+fn parse_stmt<'a>(words: &[&'a str], symbols: &mut Symbols<'a>) -> Result<Option<Stmt<'a>>> {
+    let stmt = match words {
+        [] | ["#ip", _] => return Ok(None),
+        ["alias", reg, name] => {
+            let reg = symbols.register(reg)?;
+            if symbols.aliases.insert(name, reg).is_some() {
+                bail!("Alias '{}' defined twice", name);
+            }
+            return Ok(None);
+        }
+        ["scratch", reg] => {
+            symbols.scratch = Some(symbols.register(reg)?);
+            return Ok(None);
+        }
+        ["jmp", label] => Stmt::Jmp(label),
+        ["jz", reg, label] => Stmt::Jz(reg, label),
+        [op, a, b, c] => Stmt::Inst(op.parse()?, [a, b, c]),
+        [cmd, ..] => bail!("Can't parse '{}' with {} operands", cmd, words.len() - 1),
+    };
+    Ok(Some(stmt))
+}
+
+/// Assembles a program with labels, register aliases, comments and jump
+/// macros into plain Elfcode:
+///
+/// - `; comment` anywhere on a line
+/// - `label:` in front of an instruction, usable as an immediate operand
+/// - `alias r3 counter` names a register, `ip` is the one bound by the `#ip`
+///   line, wherever that is
+/// - `jmp label` jumps unconditionally
+/// - `jz reg label` jumps if `reg` is zero, using the register declared by
+///   `scratch <reg>` for the comparison
+///
+/// Plain Elfcode assembles to itself.
+pub fn assemble(source: &str) -> Result<Program> {
+    let mut symbols = Symbols {
+        ip: find_ip(source)?,
+        ..Symbols::default()
+    };
+    let mut stmts = vec![];
+    let mut pc = 0;
+    for (i, line) in source.lines().enumerate() {
+        let (labels, words) = split_line(line);
+        let stmt = parse_stmt(&words, &mut symbols).map_err(|e| format_err!("{}: {}", i + 1, e))?;
+        for label in labels {
+            if symbols.labels.insert(label, pc).is_some() {
+                bail!("{}: Label '{}' defined twice", i + 1, label);
+            }
+        }
+        if let Some(stmt) = stmt {
+            pc += stmt.len();
+            stmts.push((i + 1, stmt));
+        }
+    }
+
+    let mut code = vec![];
+    for (line, stmt) in &stmts {
+        let pc = code.len();
+        symbols
+            .lower(stmt, pc, &mut code)
+            .map_err(|e| format_err!("{}: {}", line, e))?;
+    }
+    Ok(Program {
+        ip: symbols.ip,
+        code,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Machine;
+    use super::*;
+
+    #[test]
+    fn sum() {
+        let source = "
+#ip 5
+alias r0 total
+alias r1 i
+scratch r4
+        seti 0 0 total
+loop:   addi i 1 i          ; add up 1 to 4
+        addr total i total
+        eqri i 4 r2
+        jz r2 loop
+        jmp done
+        seti 99 0 r3        ; skipped
+done:   seti done 0 r3      ; labels are also immediates
+";
+        let program = assemble(source).unwrap();
+        assert_eq!(
+            program.to_string(),
+            "#ip 5
+seti 0 0 0
+addi 1 1 1
+addr 0 1 0
+eqri 1 4 2
+eqri 2 0 4
+addr 4 5 5
+seti 7 0 5
+seti 0 0 5
+seti 9 0 5
+seti 99 0 3
+seti 10 0 3
+"
+        );
+        let mut machine = Machine::new(&program, 6).unwrap();
+        machine.run();
+        assert_eq!(machine.reg[..4], [10, 4, 1, 10]);
+    }
+
+    #[test]
+    fn ip_alias() {
+        let source = "
+alias ip pc
+alias r3 here
+#ip 3
+        addi here 1 pc      ; skips the next instruction
+        seti 99 0 r0
+        addr pc 0 r1
+        seti 7 0 here       ; jumps past the end
+";
+        let program = assemble(source).unwrap();
+        assert_eq!(
+            program.to_string(),
+            "#ip 3
+addi 3 1 3
+seti 99 0 0
+addr 3 0 1
+seti 7 0 3
+"
+        );
+        let mut machine = Machine::new(&program, 4).unwrap();
+        machine.run();
+        assert_eq!(machine.reg, [0, 2, 0, 7]);
+        assert_eq!(
+            assemble("#ip 1\n#ip 2").unwrap_err().to_string(),
+            "2: More than one #ip line"
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let err = |source: &str| assemble(source).unwrap_err().to_string();
+        assert_eq!(
+            err("#ip 1\nstart: jmp start"),
+            "2: Can't jump to 'start' at address 0"
+        );
+        assert_eq!(err("addi 0 1 0\nx: jmp x"), "2: Jumps need an #ip line");
+        assert_eq!(err("#ip 1\naddi 0 x 0"), "2: Unknown label or value 'x'");
+        assert_eq!(
+            err("#ip 1\nx: jz r0 x"),
+            "2: jz needs a scratch register, declare one with 'scratch <reg>'"
+        );
+        let plain = "#ip 3\naddi 3 16 3\nseti 1 8 1\n";
+        assert_eq!(assemble(plain).unwrap().to_string(), plain);
+    }
+}
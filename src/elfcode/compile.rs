@@ -0,0 +1,575 @@
+use super::disasm::is_comparison;
+use super::optimize::{find_idioms, Fused};
+use super::{Inst, Op, Program};
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// The most registers a compiled program can use.
+pub const MAX_REGS: usize = 16;
+const MASK: usize = MAX_REGS - 1;
+/// The most blocks in a loop compiled into a single closure.
+const MAX_LOOP: usize = 3;
+type Regs = [usize; MAX_REGS];
+
+/// A compiled block, returning the address to continue at.
+type Code = Box<dyn Fn(&mut Regs) -> usize>;
+
+/// How a block picks the address to continue at.
+#[derive(Clone)]
+enum Exit {
+    To(usize),
+    /// `addr` of the instruction pointer and a register, at address `pc`.
+    Relative {
+        pc: usize,
+        reg: usize,
+    },
+    /// A comparison followed by a relative jump on its result.
+    Branch {
+        cmp: Inst,
+        if_true: usize,
+        if_false: usize,
+    },
+    Fused(Fused),
+    /// Any other instruction writing the instruction pointer, executed the
+    /// slow way.
+    Inst {
+        pc: usize,
+        inst: Inst,
+    },
+}
+
+#[derive(Clone)]
+struct Block {
+    body: Vec<Inst>,
+    exit: Exit,
+}
+
+/// A program translated into chains of pre-decoded closures. There is a
+/// block for every address, running up to the end of its basic block, so
+/// that computed jumps can land anywhere.
+///
+/// When the instruction pointer register is only written by jumps, reads of
+/// it are folded into constants and it isn't updated before every
+/// instruction, only when the run stops.
+///
+/// Loops of up to `MAX_LOOP` blocks run inside a single closure until they
+/// leave the loop.
+pub struct Compiled {
+    ip: Option<usize>,
+    num_regs: usize,
+    fast: bool,
+    breakpoints: Vec<bool>,
+    blocks: Vec<Code>,
+}
+
+/// Evaluates an instruction whose register operands all are the instruction
+/// pointer.
+fn eval_at(inst: &Inst, ip: usize, pc: usize) -> usize {
+    let mut reg = vec![0; ip.max(inst.c) + 1];
+    reg[ip] = pc;
+    inst.exec(&mut reg);
+    reg[inst.c]
+}
+
+/// Replaces reads of the instruction pointer register by its value `pc`.
+fn fold_ip(inst: Inst, ip: usize, pc: usize) -> Inst {
+    let (op, a, b, c) = (inst.op, inst.a, inst.b, inst.c);
+    let ip_a = op.a_is_reg() && a == ip;
+    let ip_b = op.b_is_reg() && b == ip;
+    if !ip_a && !ip_b {
+        return inst;
+    }
+    let other_a = op.a_is_reg() && !ip_a;
+    let other_b = op.b_is_reg() && !ip_b;
+    if !other_a && !other_b {
+        return Inst::new(Op::SetI, eval_at(&inst, ip, pc), 0, c);
+    }
+    let other = if ip_a { b } else { a };
+    match op {
+        Op::AddR => Inst::new(Op::AddI, other, pc, c),
+        Op::MulR => Inst::new(Op::MulI, other, pc, c),
+        Op::BanR => Inst::new(Op::BanI, other, pc, c),
+        Op::BorR => Inst::new(Op::BorI, other, pc, c),
+        Op::GtRR if ip_a => Inst::new(Op::GtIR, pc, b, c),
+        Op::GtRR => Inst::new(Op::GtRI, a, pc, c),
+        Op::EqRR if ip_a => Inst::new(Op::EqIR, pc, b, c),
+        Op::EqRR => Inst::new(Op::EqRI, a, pc, c),
+        _ => unreachable!(),
+    }
+}
+
+/// Calls `$body` with `$k` bound to the number of `$op` as a constant, so
+/// that generic code gets a copy specialised for each instruction. The
+/// numbers are those `eval` understands.
+macro_rules! with_op {
+    ($op:expr, $k:ident => $body:expr) => {
+        with_op!(@arms $op, $k, $body,
+            AddR 0 AddI 1 MulR 2 MulI 3 BanR 4 BanI 5 BorR 6 BorI 7
+            SetR 8 SetI 9 GtIR 10 GtRI 11 GtRR 12 EqIR 13 EqRI 14 EqRR 15)
+    };
+    (@arms $op:expr, $k:ident, $body:expr, $($name:ident $n:literal)*) => {
+        match $op {
+            $(Op::$name => {
+                const $k: u8 = $n;
+                $body
+            })*
+        }
+    };
+}
+
+/// The result of the instruction `with_op!` numbers `K`. Arithmetic wraps
+/// around like that of a `Machine`.
+#[inline(always)]
+fn eval<const K: u8>(r: &Regs, a: usize, b: usize) -> usize {
+    let (ra, rb) = (a & MASK, b & MASK);
+    match K {
+        0 => r[ra].wrapping_add(r[rb]),
+        1 => r[ra].wrapping_add(b),
+        2 => r[ra].wrapping_mul(r[rb]),
+        3 => r[ra].wrapping_mul(b),
+        4 => r[ra] & r[rb],
+        5 => r[ra] & b,
+        6 => r[ra] | r[rb],
+        7 => r[ra] | b,
+        8 => r[ra],
+        9 => a,
+        10 => (a > r[rb]) as usize,
+        11 => (r[ra] > b) as usize,
+        12 => (r[ra] > r[rb]) as usize,
+        13 => (a == r[rb]) as usize,
+        14 => (r[ra] == b) as usize,
+        _ => (r[ra] == r[rb]) as usize,
+    }
+}
+
+fn op<const K: u8>(i: Inst, next: Code) -> Code {
+    let c = i.c & MASK;
+    Box::new(move |r| {
+        r[c] = eval::<K>(r, i.a, i.b);
+        next(r)
+    })
+}
+
+fn op_op<const K1: u8, const K2: u8>(i1: Inst, i2: Inst, next: Code) -> Code {
+    let (c1, c2) = (i1.c & MASK, i2.c & MASK);
+    Box::new(move |r| {
+        r[c1] = eval::<K1>(r, i1.a, i1.b);
+        r[c2] = eval::<K2>(r, i2.a, i2.b);
+        next(r)
+    })
+}
+
+fn branch<const C: u8>(cmp: Inst, if_true: usize, if_false: usize) -> Code {
+    let c = cmp.c & MASK;
+    Box::new(move |r| {
+        r[c] = eval::<C>(r, cmp.a, cmp.b);
+        if r[c] != 0 {
+            if_true
+        } else {
+            if_false
+        }
+    })
+}
+
+fn op_branch<const K: u8, const C: u8>(
+    i: Inst,
+    cmp: Inst,
+    if_true: usize,
+    if_false: usize,
+) -> Code {
+    let (c1, c2) = (i.c & MASK, cmp.c & MASK);
+    Box::new(move |r| {
+        r[c1] = eval::<K>(r, i.a, i.b);
+        r[c2] = eval::<C>(r, cmp.a, cmp.b);
+        if r[c2] != 0 {
+            if_true
+        } else {
+            if_false
+        }
+    })
+}
+
+fn compile_block(block: Block, ip: Option<usize>) -> Code {
+    let mut body = block.body;
+    let mut next: Code = match block.exit {
+        Exit::To(next) => Box::new(move |_| next),
+        Exit::Relative { pc, reg } => Box::new(move |r| (pc + 1).saturating_add(r[reg & MASK])),
+        Exit::Branch {
+            cmp,
+            if_true,
+            if_false,
+        } => match body.pop() {
+            Some(i) => with_op!(i.op, K => with_op!(cmp.op, C => {
+                op_branch::<K, C>(i, cmp, if_true, if_false)
+            })),
+            None => with_op!(cmp.op, C => branch::<C>(cmp, if_true, if_false)),
+        },
+        Exit::Fused(f) => {
+            let ip = ip.unwrap();
+            Box::new(move |r| f.exec(r, ip))
+        }
+        Exit::Inst { pc, inst } => {
+            let ip = ip.unwrap();
+            let exec =
+                with_op!(inst.op, K => op::<K>(inst, Box::new(move |r| r[ip].saturating_add(1))));
+            Box::new(move |r| {
+                r[ip] = pc;
+                exec(r)
+            })
+        }
+    };
+    // Pair up the instructions from the end, so each closure runs two.
+    while let Some(i2) = body.pop() {
+        next = match body.pop() {
+            Some(i1) => with_op!(i1.op, K1 => with_op!(i2.op, K2 => {
+                op_op::<K1, K2>(i1, i2, next)
+            })),
+            None => with_op!(i2.op, K => op::<K>(i2, next)),
+        };
+    }
+    next
+}
+
+/// The addresses a block can continue at, if they are known in advance.
+fn successors(block: &Block) -> Vec<usize> {
+    match block.exit {
+        Exit::To(next) => vec![next],
+        Exit::Branch {
+            if_true, if_false, ..
+        } => vec![if_false, if_true],
+        _ => vec![],
+    }
+}
+
+/// Finds a cycle of at most `MAX_LOOP` blocks leading from `start` back to
+/// it, without breakpoints in between.
+fn find_loop(blocks: &[Block], breakpoints: &[bool], path: &mut Vec<usize>) -> bool {
+    let start = path[0];
+    for next in successors(&blocks[*path.last().unwrap()]) {
+        if next == start {
+            return true;
+        }
+        if next < blocks.len()
+            && !breakpoints[next]
+            && path.len() < MAX_LOOP
+            && !path.contains(&next)
+        {
+            path.push(next);
+            if find_loop(blocks, breakpoints, path) {
+                return true;
+            }
+            path.pop();
+        }
+    }
+    false
+}
+
+/// Runs the blocks of a loop in turn for as long as each continues with the
+/// next one, so that the loop doesn't go through `Compiled::run`.
+fn compile_loop(segments: Vec<(Code, usize)>) -> Code {
+    Box::new(move |r| loop {
+        for (code, expected) in &segments {
+            let pc = code(r);
+            if pc != *expected {
+                return pc;
+            }
+        }
+    })
+}
+
+/// How a jump in the fast path computes its target, if it can be run
+/// without keeping the instruction pointer register up to date.
+fn fast_exit(inst: &Inst, ip: usize, pc: usize) -> Option<Exit> {
+    let reads_a = inst.op.a_is_reg() && inst.a != ip;
+    let reads_b = inst.op.b_is_reg() && inst.b != ip;
+    match (inst.op, reads_a, reads_b) {
+        (_, false, false) => Some(Exit::To(eval_at(inst, ip, pc).saturating_add(1))),
+        (Op::AddR, true, false) => Some(Exit::Relative { pc, reg: inst.a }),
+        (Op::AddR, false, true) => Some(Exit::Relative { pc, reg: inst.b }),
+        _ => None,
+    }
+}
+
+impl Compiled {
+    /// Compiles the program for a machine with `num_regs` registers, with
+    /// runs stopping at the addresses in `breakpoints`. With `optimize` the
+    /// loops the optimiser knows are replaced by super-instructions.
+    pub fn new(
+        program: &Program,
+        num_regs: usize,
+        breakpoints: &[usize],
+        optimize: bool,
+    ) -> Result<Compiled> {
+        let ip = program.ip;
+        if let Some(ip) = ip {
+            if ip >= num_regs {
+                bail!("Instruction pointer bound to missing register {}", ip);
+            }
+        }
+        if num_regs > MAX_REGS {
+            bail!("Can't compile for more than {} registers", MAX_REGS);
+        }
+        for inst in &program.code {
+            inst.check(num_regs)?;
+        }
+        let code = &program.code;
+        let fused: HashMap<usize, Fused> = if optimize {
+            find_idioms(code, ip).into_iter().collect()
+        } else {
+            HashMap::new()
+        };
+        let breakpoints: Vec<bool> = (0..=code.len())
+            .map(|pc| breakpoints.contains(&pc))
+            .collect();
+        let writes_ip = |inst: &Inst| Some(inst.c) == ip;
+        let fast = match ip {
+            Some(ip) => code
+                .iter()
+                .enumerate()
+                .all(|(pc, inst)| !writes_ip(inst) || fast_exit(inst, ip, pc).is_some()),
+            None => true,
+        };
+
+        let mut blocks = Vec::with_capacity(code.len());
+        for start in 0..code.len() {
+            if let Some(f) = fused.get(&start) {
+                blocks.push(Block {
+                    body: vec![],
+                    exit: Exit::Fused(f.clone()),
+                });
+                continue;
+            }
+            let mut body = vec![];
+            let mut pc = start;
+            let exit = loop {
+                if pc >= code.len() || (pc > start && (breakpoints[pc] || fused.contains_key(&pc)))
+                {
+                    break Exit::To(pc);
+                }
+                let inst = code[pc];
+                match (ip, fast) {
+                    (Some(ip), true) if writes_ip(&inst) => {
+                        break fast_exit(&inst, ip, pc).unwrap()
+                    }
+                    (Some(ip), true) => body.push(fold_ip(inst, ip, pc)),
+                    (Some(_), false) => break Exit::Inst { pc, inst },
+                    (None, _) => body.push(inst),
+                }
+                pc += 1;
+            };
+            let exit = match exit {
+                Exit::Relative { pc, reg } if body.last().map(|i| i.c) == Some(reg) => {
+                    let cmp = *body.last().unwrap();
+                    if is_comparison(cmp.op) {
+                        body.pop();
+                        Exit::Branch {
+                            cmp,
+                            if_true: pc + 2,
+                            if_false: pc + 1,
+                        }
+                    } else {
+                        Exit::Relative { pc, reg }
+                    }
+                }
+                exit => exit,
+            };
+            blocks.push(Block { body, exit });
+        }
+
+        // Jump straight to the target of blocks that only jump on.
+        let thread = |mut pc: usize| {
+            for _ in 0..blocks.len() {
+                match blocks.get(pc) {
+                    Some(Block {
+                        body,
+                        exit: Exit::To(next),
+                    }) if body.is_empty() && !breakpoints[pc] => pc = *next,
+                    _ => break,
+                }
+            }
+            pc
+        };
+        let targets: Vec<Exit> = blocks
+            .iter()
+            .map(|block| match block.exit {
+                Exit::To(next) => Exit::To(thread(next)),
+                Exit::Branch {
+                    cmp,
+                    if_true,
+                    if_false,
+                } => Exit::Branch {
+                    cmp,
+                    if_true: thread(if_true),
+                    if_false: thread(if_false),
+                },
+                _ => Exit::To(0),
+            })
+            .collect();
+        for (block, target) in blocks.iter_mut().zip(targets) {
+            if let Exit::To(_) | Exit::Branch { .. } = block.exit {
+                block.exit = target;
+            }
+        }
+        let compiled = (0..blocks.len())
+            .map(|start| {
+                let mut path = vec![start];
+                if breakpoints[start] || !find_loop(&blocks, &breakpoints, &mut path) {
+                    return compile_block(blocks[start].clone(), ip);
+                }
+                let segments = path
+                    .iter()
+                    .zip(path.iter().skip(1).chain(Some(&start)))
+                    .map(|(&pc, &next)| (compile_block(blocks[pc].clone(), ip), next))
+                    .collect();
+                compile_loop(segments)
+            })
+            .collect();
+        Ok(Compiled {
+            ip,
+            num_regs,
+            fast,
+            breakpoints,
+            blocks: compiled,
+        })
+    }
+
+    /// Runs from address `pc` on the registers `reg` until the program halts
+    /// or reaches a breakpoint, returning the address it stopped at.
+    pub fn run(&self, reg: &mut [usize], mut pc: usize) -> usize {
+        assert_eq!(reg.len(), self.num_regs);
+        let mut r: Regs = [0; MAX_REGS];
+        r[..reg.len()].copy_from_slice(reg);
+        let mut moved = false;
+        while let Some(block) = self.blocks.get(pc) {
+            pc = block(&mut r);
+            moved = true;
+            if self.breakpoints.get(pc) == Some(&true) {
+                break;
+            }
+        }
+        // Leave the instruction pointer register as the interpreter would.
+        if let (Some(ip), true, true) = (self.ip, self.fast, moved) {
+            r[ip] = pc - 1;
+        }
+        reg.copy_from_slice(&r[..reg.len()]);
+        pc
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::Machine;
+    use super::*;
+
+    fn check(text: &str, reg: &[usize]) {
+        let program: Program = text.parse().unwrap();
+        let mut machine = Machine::new(&program, reg.len()).unwrap();
+        machine.reg.copy_from_slice(reg);
+        machine.run();
+        let compiled = Compiled::new(&program, reg.len(), &[], false).unwrap();
+        let mut compiled_reg = reg.to_vec();
+        assert_eq!(compiled.run(&mut compiled_reg, 0), machine.pc);
+        assert_eq!(compiled_reg, machine.reg);
+    }
+
+    #[test]
+    fn fast_path() {
+        let program = "#ip 5
+seti 0 0 1
+seti 0 0 2
+addi 3 1 3
+addr 3 5 0
+addi 2 1 2
+gtri 2 3 4
+addr 4 5 5
+seti 1 0 5
+addi 1 1 1
+gtri 1 2 4
+addr 5 4 5
+seti 0 0 5";
+        check(program, &[0; 6]);
+        let compiled = Compiled::new(&program.parse().unwrap(), 6, &[8], false).unwrap();
+        assert!(compiled.fast);
+        let mut reg = [0; 6];
+        assert_eq!(compiled.run(&mut reg, 0), 8);
+        assert_eq!(reg, [7, 0, 4, 4, 1, 7]);
+        assert_eq!(compiled.run(&mut reg, 8), 8);
+    }
+
+    #[test]
+    fn slow_path() {
+        // Jumps through a table in r1 and reads the instruction pointer.
+        let program = "#ip 0
+seti 3 0 1
+setr 1 0 0
+seti 9 0 2
+addr 0 1 3
+mulr 0 0 0";
+        check(program, &[0; 4]);
+        let compiled = Compiled::new(&program.parse().unwrap(), 4, &[], false).unwrap();
+        assert!(!compiled.fast);
+    }
+
+    #[test]
+    fn no_ip() {
+        check("seti 7 0 1\naddr 1 1 0\nmuli 0 3 2", &[0; 4]);
+    }
+
+    #[test]
+    fn wrapping() {
+        check(
+            "addi 0 1 0\nmulr 1 1 1\naddr 1 2 2",
+            &[usize::MAX, 1 << 40, usize::MAX],
+        );
+    }
+
+    /// Times the interpreter and the compiled code on the programs of days
+    /// 19 and 21, without fusing loops. Run it with
+    /// `cargo test --release -- --ignored --nocapture speedup`.
+    #[test]
+    #[ignore]
+    fn speedup() {
+        use crate::prelude::AocData;
+        use std::time::Instant;
+
+        // Day 19 runs to the end. Day 21 never halts with r0 at its maximum,
+        // so it stops after comparing r0 300 times.
+        for &(day, r0, stops) in &[(19, 0, 1), (21, usize::MAX, 300)] {
+            let program = Program::load(AocData::new(day)).unwrap();
+            let check = program
+                .code
+                .iter()
+                .position(|inst| inst.op == Op::EqRR && (inst.a == 0 || inst.b == 0));
+            let breakpoints: Vec<usize> = check.filter(|_| stops > 1).into_iter().collect();
+            let start = [r0, 0, 0, 0, 0, 0];
+
+            let time = Instant::now();
+            let mut machine = Machine::new(&program, 6).unwrap();
+            machine.reg.copy_from_slice(&start);
+            for _ in 0..stops {
+                machine.step();
+                while !machine.is_halted() && !breakpoints.contains(&machine.pc) {
+                    machine.step();
+                }
+            }
+            let interpreted = time.elapsed();
+
+            let time = Instant::now();
+            let compiled = Compiled::new(&program, 6, &breakpoints, false).unwrap();
+            let mut reg = start;
+            let mut pc = 0;
+            for _ in 0..stops {
+                pc = compiled.run(&mut reg, pc);
+            }
+            let compiled_time = time.elapsed();
+
+            assert_eq!((pc, &reg[..]), (machine.pc, &machine.reg[..]));
+            let speedup = interpreted.as_secs_f64() / compiled_time.as_secs_f64();
+            println!(
+                "Day {}: interpreted {:?}, compiled {:?}, {:.1}x faster",
+                day, interpreted, compiled_time, speedup
+            );
+            assert!(speedup >= 5.0, "Day {} is only {:.1}x faster", day, speedup);
+        }
+    }
+}
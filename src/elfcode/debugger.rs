@@ -0,0 +1,271 @@
+use super::{Machine, Program};
+use crate::prelude::*;
+use std::collections::BTreeSet;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Write};
+
+const HELP: &str = "\
+step [n]       execute n instructions (default 1), also an empty line
+continue       run until a breakpoint, a watched register changes or halt
+break <n>      toggle a breakpoint on instruction n
+watch <r>      toggle a watchpoint on register r
+set <r> <v>    set register r to v
+regs           print the registers
+hist           print how often each instruction was executed
+list           print the program around the current instruction
+reset          restart the program from the initial registers
+quit";
+
+/// Why `continue` stopped.
+enum Stop {
+    Halted,
+    Breakpoint,
+    Watch(usize, usize, usize),
+}
+
+pub struct Debugger {
+    machine: Machine,
+    initial: Machine,
+    breakpoints: BTreeSet<usize>,
+    watches: BTreeSet<usize>,
+    counts: Vec<u64>,
+    steps: u64,
+}
+
+impl Debugger {
+    pub fn new(program: &Program, reg: &[usize]) -> Result<Debugger> {
+        let mut machine = Machine::new(program, reg.len())?;
+        machine.reg.copy_from_slice(reg);
+        Ok(Debugger {
+            initial: machine.clone(),
+            counts: vec![0; machine.code.len()],
+            machine,
+            breakpoints: BTreeSet::new(),
+            watches: BTreeSet::new(),
+            steps: 0,
+        })
+    }
+
+    fn step(&mut self) -> bool {
+        let pc = self.machine.pc;
+        if !self.machine.step() {
+            return false;
+        }
+        self.counts[pc] += 1;
+        self.steps += 1;
+        true
+    }
+
+    fn run(&mut self) -> Stop {
+        loop {
+            let before: Vec<usize> = self.watches.iter().map(|&r| self.machine.reg[r]).collect();
+            if !self.step() {
+                return Stop::Halted;
+            }
+            for (&r, &old) in self.watches.iter().zip(&before) {
+                if self.machine.reg[r] != old {
+                    return Stop::Watch(r, old, self.machine.reg[r]);
+                }
+            }
+            if self.breakpoints.contains(&self.machine.pc) {
+                return Stop::Breakpoint;
+            }
+        }
+    }
+
+    fn register(&self, arg: Option<&str>) -> Result<usize> {
+        let arg = arg.ok_or_else(|| format_err!("Missing register"))?;
+        let r: usize = arg.trim_start_matches('r').parse()?;
+        if r >= self.machine.reg.len() {
+            bail!("No register {}", r);
+        }
+        Ok(r)
+    }
+
+    fn current(&self, out: &mut String) {
+        match self.machine.code.get(self.machine.pc) {
+            Some(inst) => writeln!(out, "=> {:02}  {}", self.machine.pc, inst).unwrap(),
+            None => writeln!(out, "Halted after {} steps", self.steps).unwrap(),
+        }
+    }
+
+    fn regs(&self, out: &mut String) {
+        write!(out, "pc={}", self.machine.pc).unwrap();
+        for (r, v) in self.machine.reg.iter().enumerate() {
+            let ip = if Some(r) == self.machine.ip {
+                "(ip)"
+            } else {
+                ""
+            };
+            write!(out, " r{}{}={}", r, ip, v).unwrap();
+        }
+        writeln!(out, "  steps={}", self.steps).unwrap();
+    }
+
+    fn hist(&self, out: &mut String) {
+        let max = self.counts.iter().cloned().max().unwrap_or(0).max(1);
+        for (pc, (&count, inst)) in self.counts.iter().zip(&self.machine.code).enumerate() {
+            let bar = "#".repeat((count * 40).div_ceil(max) as usize);
+            writeln!(
+                out,
+                "{:02} {:>12}  {:<20} {}",
+                pc,
+                count,
+                inst.to_string(),
+                bar
+            )
+            .unwrap();
+        }
+    }
+
+    fn list(&self, out: &mut String) {
+        let pc = self.machine.pc;
+        let end = (pc + 6).min(self.machine.code.len());
+        for i in pc.saturating_sub(5)..end {
+            let marker = if i == pc { "=>" } else { "  " };
+            let brk = if self.breakpoints.contains(&i) {
+                "*"
+            } else {
+                " "
+            };
+            writeln!(out, "{}{}{:02}  {}", marker, brk, i, self.machine.code[i]).unwrap();
+        }
+    }
+
+    /// Executes one command line, writing its output to `out`. Returns false
+    /// once the user asked to quit.
+    pub fn command(&mut self, line: &str, out: &mut String) -> Result<bool> {
+        let mut words = line.split_whitespace();
+        let cmd = words.next().unwrap_or("step");
+        let arg = words.next();
+        match cmd {
+            "s" | "step" => {
+                let n: usize = arg.map_or(Ok(1), str::parse)?;
+                for _ in 0..n {
+                    if !self.step() {
+                        break;
+                    }
+                }
+                self.current(out);
+            }
+            "c" | "continue" => {
+                match self.run() {
+                    Stop::Halted => {}
+                    Stop::Breakpoint => writeln!(out, "Breakpoint").unwrap(),
+                    Stop::Watch(r, old, new) => {
+                        writeln!(out, "r{} changed from {} to {}", r, old, new).unwrap()
+                    }
+                }
+                self.current(out);
+            }
+            "b" | "break" => {
+                let pc: usize = arg
+                    .ok_or_else(|| format_err!("Missing instruction index"))?
+                    .parse()?;
+                if pc >= self.machine.code.len() {
+                    bail!("No instruction {}", pc);
+                }
+                if self.breakpoints.remove(&pc) {
+                    writeln!(out, "Removed breakpoint on {:02}", pc).unwrap();
+                } else {
+                    self.breakpoints.insert(pc);
+                    writeln!(out, "Breakpoint on {:02}", pc).unwrap();
+                }
+            }
+            "w" | "watch" => {
+                let r = self.register(arg)?;
+                if self.watches.remove(&r) {
+                    writeln!(out, "Removed watchpoint on r{}", r).unwrap();
+                } else {
+                    self.watches.insert(r);
+                    writeln!(out, "Watching r{}", r).unwrap();
+                }
+            }
+            "set" => {
+                let r = self.register(arg)?;
+                let v = words
+                    .next()
+                    .ok_or_else(|| format_err!("Missing value"))?
+                    .parse()?;
+                self.machine.reg[r] = v;
+                self.regs(out);
+            }
+            "r" | "regs" => self.regs(out),
+            "h" | "hist" => self.hist(out),
+            "l" | "list" => self.list(out),
+            "reset" => {
+                self.machine = self.initial.clone();
+                self.counts.iter_mut().for_each(|c| *c = 0);
+                self.steps = 0;
+                self.current(out);
+            }
+            "q" | "quit" => return Ok(false),
+            "help" => writeln!(out, "{}", HELP).unwrap(),
+            _ => bail!("Unknown command '{}', try help", cmd),
+        }
+        Ok(true)
+    }
+}
+
+/// Debugs the program interactively from the registers `reg`, reading
+/// commands from stdin.
+pub fn debug(program: &Program, reg: &[usize]) -> Result<()> {
+    let mut debugger = Debugger::new(program, reg)?;
+    let mut out = String::new();
+    debugger.current(&mut out);
+    print!("{}", out);
+    let stdin = io::stdin();
+    loop {
+        print!("(elf) ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        out.clear();
+        match debugger.command(&line, &mut out) {
+            Ok(true) => print!("{}", out),
+            Ok(false) => break,
+            Err(err) => println!("error: {}", err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn commands() {
+        let program: Program = "#ip 0
+seti 5 0 1
+seti 6 0 2
+addi 0 1 0
+addr 1 2 3
+setr 1 0 0
+seti 8 0 4
+seti 9 0 5"
+            .parse()
+            .unwrap();
+        let mut debugger = Debugger::new(&program, &[0; 6]).unwrap();
+        let mut run = |cmd: &str| {
+            let mut out = String::new();
+            assert!(debugger.command(cmd, &mut out).unwrap());
+            out
+        };
+        assert_eq!(run("step 2"), "=> 02  addi 0 1 0\n");
+        run("break 6");
+        assert_eq!(run("c"), "Breakpoint\n=> 06  seti 9 0 5\n");
+        run("watch r5");
+        assert_eq!(run("c"), "r5 changed from 0 to 9\nHalted after 5 steps\n");
+        assert_eq!(
+            run("regs"),
+            "pc=7 r0(ip)=6 r1=5 r2=6 r3=0 r4=0 r5=9  steps=5\n"
+        );
+        assert!(run("hist").starts_with("00            1"));
+        assert_eq!(run("reset"), "=> 00  seti 5 0 1\n");
+        assert!(debugger.command("set r9 1", &mut String::new()).is_err());
+        assert!(!debugger.command("quit", &mut String::new()).unwrap());
+    }
+}
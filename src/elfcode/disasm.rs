@@ -0,0 +1,272 @@
+use super::{Inst, Op, Program};
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+/// Where control goes after an instruction.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Flow {
+    /// Falls through to the next instruction.
+    Next,
+    /// Always continues at the given address.
+    Goto(usize),
+    /// Continues at `target` if register `cond` is 1, at the next
+    /// instruction if it is 0.
+    Branch { cond: usize, target: usize },
+    /// Computes the next address from other registers.
+    Indirect,
+}
+
+/// Works out the control flow of every instruction of the program, folding
+/// reads of the instruction pointer register into constants.
+pub fn flows(program: &Program) -> Vec<Flow> {
+    let ip = match program.ip {
+        Some(ip) => ip,
+        None => return vec![Flow::Next; program.code.len()],
+    };
+    let writes_cmp = |pc: usize, reg: usize| match pc.checked_sub(1) {
+        Some(prev) => {
+            let inst = &program.code[prev];
+            inst.c == reg && is_comparison(inst.op)
+        }
+        None => false,
+    };
+    program
+        .code
+        .iter()
+        .enumerate()
+        .map(|(pc, inst)| {
+            if inst.c != ip {
+                return Flow::Next;
+            }
+            let reads_a = inst.op.a_is_reg() && inst.a != ip;
+            let reads_b = inst.op.b_is_reg() && inst.b != ip;
+            if !reads_a && !reads_b {
+                let mut reg = vec![0; ip + 1];
+                reg[ip] = pc;
+                inst.exec(&mut reg);
+                return Flow::Goto(reg[ip] + 1);
+            }
+            if inst.op == Op::AddR {
+                let other = if reads_a { inst.a } else { inst.b };
+                if !(reads_a && reads_b) && writes_cmp(pc, other) {
+                    return Flow::Branch {
+                        cond: other,
+                        target: pc + 2,
+                    };
+                }
+            }
+            Flow::Indirect
+        })
+        .collect()
+}
+
+pub fn is_comparison(op: Op) -> bool {
+    matches!(
+        op,
+        Op::GtIR | Op::GtRI | Op::GtRR | Op::EqIR | Op::EqRI | Op::EqRR
+    )
+}
+
+/// The first instruction of every basic block.
+fn leaders(program: &Program, flows: &[Flow]) -> BTreeSet<usize> {
+    let mut leaders = BTreeSet::new();
+    leaders.insert(0);
+    for (pc, flow) in flows.iter().enumerate() {
+        match *flow {
+            Flow::Next => continue,
+            Flow::Goto(target) | Flow::Branch { target, .. } => {
+                leaders.insert(target);
+            }
+            Flow::Indirect => {}
+        }
+        leaders.insert(pc + 1);
+    }
+    leaders.retain(|&pc| pc < program.code.len());
+    leaders
+}
+
+/// Renders an instruction as an assignment, with the instruction pointer
+/// register replaced by its value `pc`.
+fn statement(inst: &Inst, ip: Option<usize>, pc: usize) -> String {
+    let reg = |r: usize| {
+        if Some(r) == ip {
+            pc.to_string()
+        } else {
+            format!("r{}", r)
+        }
+    };
+    let (a, b) = (inst.a, inst.b);
+    let expr = match inst.op {
+        Op::AddR => format!("{} + {}", reg(a), reg(b)),
+        Op::AddI => format!("{} + {}", reg(a), b),
+        Op::MulR => format!("{} * {}", reg(a), reg(b)),
+        Op::MulI => format!("{} * {}", reg(a), b),
+        Op::BanR => format!("{} & {}", reg(a), reg(b)),
+        Op::BanI => format!("{} & {}", reg(a), b),
+        Op::BorR => format!("{} | {}", reg(a), reg(b)),
+        Op::BorI => format!("{} | {}", reg(a), b),
+        Op::SetR => reg(a),
+        Op::SetI => a.to_string(),
+        Op::GtIR => format!("{} > {}", a, reg(b)),
+        Op::GtRI => format!("{} > {}", reg(a), b),
+        Op::GtRR => format!("{} > {}", reg(a), reg(b)),
+        Op::EqIR => format!("{} == {}", a, reg(b)),
+        Op::EqRI => format!("{} == {}", reg(a), b),
+        Op::EqRR => format!("{} == {}", reg(a), reg(b)),
+    };
+    if Some(inst.c) == ip {
+        format!("goto {} + 1", expr)
+    } else if is_comparison(inst.op) {
+        format!("r{} = ({})", inst.c, expr)
+    } else {
+        format!("r{} = {}", inst.c, expr)
+    }
+}
+
+fn jump(target: usize, len: usize) -> String {
+    if target >= len {
+        "halt".to_string()
+    } else {
+        format!("goto {:02}", target)
+    }
+}
+
+/// Pseudo-code for a single instruction.
+fn pseudo_code(program: &Program, flows: &[Flow], pc: usize) -> String {
+    let len = program.code.len();
+    match flows[pc] {
+        Flow::Next | Flow::Indirect => statement(&program.code[pc], program.ip, pc),
+        Flow::Goto(target) => jump(target, len),
+        Flow::Branch { cond, target } => format!("if r{} {}", cond, jump(target, len)),
+    }
+}
+
+/// Prints the program as pseudo-code, one line per instruction with the
+/// original instruction as a comment, and basic blocks separated by the
+/// addresses jumping to them.
+pub fn disassemble(program: &Program) -> String {
+    let flows = flows(program);
+    let leaders = leaders(program, &flows);
+    let mut sources = vec![vec![]; program.code.len()];
+    for (pc, flow) in flows.iter().enumerate() {
+        if let Flow::Goto(target) | Flow::Branch { target, .. } = *flow {
+            if target < sources.len() {
+                sources[target].push(pc);
+            }
+        }
+    }
+
+    let mut out = String::new();
+    if let Some(ip) = program.ip {
+        writeln!(out, "; instruction pointer bound to r{}", ip).unwrap();
+    }
+    for (pc, inst) in program.code.iter().enumerate() {
+        if leaders.contains(&pc) && pc > 0 {
+            out.push('\n');
+        }
+        if !sources[pc].is_empty() {
+            let from: Vec<String> = sources[pc].iter().map(|s| format!("{:02}", s)).collect();
+            writeln!(out, "; from {}", from.join(", ")).unwrap();
+        }
+        let code = pseudo_code(program, &flows, pc);
+        writeln!(out, "{:02}  {:<28}; {}", pc, code, inst).unwrap();
+    }
+    out
+}
+
+/// Renders the control-flow graph of the program in Graphviz DOT format,
+/// with one node per basic block.
+pub fn to_dot(program: &Program) -> String {
+    let len = program.code.len();
+    let flows = flows(program);
+    let leaders = leaders(program, &flows);
+    let mut out = String::new();
+    out.push_str("digraph elfcode {\n");
+    out.push_str("    node [shape=box, fontname=monospace];\n");
+    let mut halts = false;
+    let mut edge = |out: &mut String, from: usize, to: usize, label: &str| {
+        let to = if to >= len {
+            halts = true;
+            "halt".to_string()
+        } else {
+            format!("b{}", to)
+        };
+        write!(out, "    b{} -> {}", from, to).unwrap();
+        if !label.is_empty() {
+            write!(out, " [label=\"{}\"]", label).unwrap();
+        }
+        out.push_str(";\n");
+    };
+
+    let blocks: Vec<usize> = leaders.iter().cloned().collect();
+    let mut indirect = false;
+    for (i, &start) in blocks.iter().enumerate() {
+        let end = blocks.get(i + 1).cloned().unwrap_or(len);
+        let mut label = String::new();
+        for pc in start..end {
+            write!(label, "{:02}: {}\\l", pc, pseudo_code(program, &flows, pc)).unwrap();
+        }
+        writeln!(out, "    b{} [label=\"{}\"];", start, label).unwrap();
+        match flows[end - 1] {
+            Flow::Next => edge(&mut out, start, end, ""),
+            Flow::Goto(target) => edge(&mut out, start, target, ""),
+            Flow::Branch { target, .. } => {
+                edge(&mut out, start, target, "true");
+                edge(&mut out, start, end, "false");
+            }
+            Flow::Indirect => {
+                indirect = true;
+                writeln!(out, "    b{} -> indirect [style=dashed];", start).unwrap();
+            }
+        }
+    }
+    if halts || blocks.is_empty() {
+        out.push_str("    halt [shape=oval];\n");
+    }
+    if indirect {
+        out.push_str("    indirect [shape=oval, label=\"?\"];\n");
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn branches() {
+        let program: Program = "#ip 4
+seti 123 0 1
+bani 1 456 1
+eqri 1 72 1
+addr 1 4 4
+seti 0 0 4
+seti 0 3 1
+mulr 4 4 4
+addr 1 4 4"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            flows(&program),
+            vec![
+                Flow::Next,
+                Flow::Next,
+                Flow::Next,
+                Flow::Branch { cond: 1, target: 5 },
+                Flow::Goto(1),
+                Flow::Next,
+                Flow::Goto(37),
+                Flow::Indirect,
+            ]
+        );
+        let text = disassemble(&program);
+        assert!(text.contains("03  if r1 goto 05"));
+        assert!(text.contains("06  halt"));
+        let dot = to_dot(&program);
+        assert!(dot.contains("b1 -> b5 [label=\"true\"]"));
+        assert!(dot.contains("b1 -> b4 [label=\"false\"]"));
+        assert!(dot.contains("b4 -> b1;"));
+        assert!(dot.contains("b5 -> halt;"));
+    }
+}
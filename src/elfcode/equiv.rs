@@ -0,0 +1,169 @@
+use super::{Machine, Program};
+use crate::prelude::*;
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+
+/// How many of the last instructions a divergence report shows.
+const TRACE_LEN: usize = 12;
+
+/// Checks a program against a native reimplementation by running both from
+/// a range of initial registers and comparing the registers they produce.
+pub struct Equivalence<'a> {
+    program: &'a Program,
+    num_regs: usize,
+    outputs: Vec<usize>,
+    max_steps: u64,
+    optimize: bool,
+}
+
+impl<'a> Equivalence<'a> {
+    /// Compares the registers `outputs` after the program halts.
+    pub fn new(program: &'a Program, num_regs: usize, outputs: &[usize]) -> Equivalence<'a> {
+        Equivalence {
+            program,
+            num_regs,
+            outputs: outputs.to_vec(),
+            max_steps: 100_000_000,
+            optimize: false,
+        }
+    }
+
+    /// Treats runs longer than `max_steps` instructions as diverging.
+    pub fn max_steps(mut self, max_steps: u64) -> Equivalence<'a> {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Runs the program with the loops `Machine::optimize` knows fused.
+    pub fn optimize(mut self) -> Equivalence<'a> {
+        self.optimize = true;
+        self
+    }
+
+    fn machine(&self, initial: &[usize]) -> Result<Machine> {
+        let mut machine = Machine::new(self.program, self.num_regs)?;
+        if initial.len() > self.num_regs {
+            bail!("Too many initial registers: {:?}", initial);
+        }
+        machine.reg[..initial.len()].copy_from_slice(initial);
+        if self.optimize {
+            machine.optimize();
+        }
+        Ok(machine)
+    }
+
+    /// Runs the program from each of the initial registers in `inputs`,
+    /// missing ones being zero, and compares its outputs with what `native`
+    /// returns for the same registers. Reports the first divergence with
+    /// the last instructions executed.
+    pub fn check<I, F>(&self, inputs: I, native: F) -> Result<()>
+    where
+        I: IntoIterator<Item = Vec<usize>>,
+        F: Fn(&[usize]) -> Vec<usize>,
+    {
+        for initial in inputs {
+            let mut machine = self.machine(&initial)?;
+            let start = machine.reg.clone();
+            let mut trace = VecDeque::with_capacity(TRACE_LEN);
+            let mut steps = 0;
+            while steps < self.max_steps {
+                let pc = machine.pc;
+                if !machine.step() {
+                    break;
+                }
+                if trace.len() == TRACE_LEN {
+                    trace.pop_front();
+                }
+                trace.push_back((pc, machine.reg.clone()));
+                steps += 1;
+            }
+
+            let problem = if machine.is_halted() {
+                let actual: Vec<usize> = self.outputs.iter().map(|&r| machine.reg[r]).collect();
+                let expected = native(&start);
+                if actual == expected {
+                    continue;
+                }
+                format!(
+                    "gives {}, native gives {}",
+                    fmt_outputs(&self.outputs, &actual),
+                    fmt_outputs(&self.outputs, &expected)
+                )
+            } else {
+                format!("didn't halt within {} steps", self.max_steps)
+            };
+            let mut report = format!(
+                "From registers {:?} the program {}\nLast instructions:\n",
+                start, problem
+            );
+            for (pc, reg) in &trace {
+                let inst = self.program.code[*pc].to_string();
+                writeln!(report, "  {:02}  {:<20} {:?}", pc, inst, reg).unwrap();
+            }
+            bail!("{}", report.trim_end());
+        }
+        Ok(())
+    }
+}
+
+fn fmt_outputs(outputs: &[usize], values: &[usize]) -> String {
+    if outputs.len() != values.len() {
+        return format!("{:?}", values);
+    }
+    let regs: Vec<String> = outputs
+        .iter()
+        .zip(values)
+        .map(|(r, v)| format!("r{}={}", r, v))
+        .collect();
+    regs.join(" ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn triangle_numbers() {
+        // r1 = 1 + 2 + ... + r0
+        let program: Program = "#ip 5
+seti 0 0 2
+addi 2 1 2
+addr 1 2 1
+eqrr 2 0 3
+addr 3 5 5
+seti 0 0 5"
+            .parse()
+            .unwrap();
+        let check = Equivalence::new(&program, 6, &[1]);
+        let inputs = || (1..20).map(|n| vec![n]);
+        check
+            .check(inputs(), |reg| vec![reg[0] * (reg[0] + 1) / 2])
+            .unwrap();
+
+        let err = check
+            .check(inputs(), |reg| vec![reg[0] * reg[0]])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(
+            err,
+            "From registers [2, 0, 0, 0, 0, 0] the program gives r1=3, native gives r1=4
+Last instructions:
+  00  seti 0 0 2           [2, 0, 0, 0, 0, 0]
+  01  addi 2 1 2           [2, 0, 1, 0, 0, 1]
+  02  addr 1 2 1           [2, 1, 1, 0, 0, 2]
+  03  eqrr 2 0 3           [2, 1, 1, 0, 0, 3]
+  04  addr 3 5 5           [2, 1, 1, 0, 0, 4]
+  05  seti 0 0 5           [2, 1, 1, 0, 0, 0]
+  01  addi 2 1 2           [2, 1, 2, 0, 0, 1]
+  02  addr 1 2 1           [2, 3, 2, 0, 0, 2]
+  03  eqrr 2 0 3           [2, 3, 2, 1, 0, 3]
+  04  addr 3 5 5           [2, 3, 2, 1, 0, 5]"
+        );
+
+        let err = Equivalence::new(&program, 6, &[1])
+            .max_steps(10)
+            .check(vec![vec![5]], |_| vec![15])
+            .unwrap_err();
+        assert!(err.to_string().contains("didn't halt within 10 steps"));
+    }
+}
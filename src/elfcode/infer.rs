@@ -0,0 +1,285 @@
+use super::{Inst, Op};
+use crate::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The most mappings `infer_opcodes` returns when the samples are ambiguous.
+pub const MAX_MAPPINGS: usize = 10;
+
+/// An instruction with its opcode still encoded as a number.
+#[derive(Clone, Copy, Debug)]
+pub struct Encoded {
+    pub op: usize,
+    pub a: usize,
+    pub b: usize,
+    pub c: usize,
+}
+
+impl Encoded {
+    pub fn decode(&self, op: Op) -> Inst {
+        Inst::new(op, self.a, self.b, self.c)
+    }
+}
+
+/// The registers before and after executing an encoded instruction.
+pub struct Sample {
+    pub before: Vec<usize>,
+    pub inst: Encoded,
+    pub after: Vec<usize>,
+}
+
+/// A set of operations as a bitmask over `Op::ALL`.
+type OpSet = u16;
+
+fn op_names(ops: OpSet) -> String {
+    let names: Vec<&str> = Op::ALL
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| ops & (1 << i) != 0)
+        .map(|(_, op)| op.name())
+        .collect();
+    if names.is_empty() {
+        "nothing".to_string()
+    } else {
+        names.join(", ")
+    }
+}
+
+/// Returns the operations which turn the registers of the sample into the
+/// expected ones, as a bitmask over `Op::ALL`.
+pub fn matching_ops(sample: &Sample) -> OpSet {
+    let mut result = 0;
+    for (i, &op) in Op::ALL.iter().enumerate() {
+        let inst = sample.inst.decode(op);
+        if inst.check(sample.before.len()).is_err() {
+            continue;
+        }
+        let mut regs = sample.before.clone();
+        inst.exec(&mut regs);
+        if regs == sample.after {
+            result |= 1 << i;
+        }
+    }
+    result
+}
+
+/// The operation each opcode of the samples stands for.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct OpcodeMap(pub BTreeMap<usize, Op>);
+
+impl OpcodeMap {
+    pub fn decode(&self, inst: &Encoded) -> Result<Inst> {
+        match self.0.get(&inst.op) {
+            Some(&op) => Ok(inst.decode(op)),
+            None => bail!("Unknown opcode {}", inst.op),
+        }
+    }
+}
+
+impl fmt::Display for OpcodeMap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, (opcode, op)) in self.0.iter().enumerate() {
+            let sep = if i == 0 { "" } else { " " };
+            write!(f, "{}{}={}", sep, opcode, op)?;
+        }
+        Ok(())
+    }
+}
+
+/// The operations each opcode can still stand for after all samples.
+struct Solver<'a> {
+    samples: &'a [Sample],
+    candidates: BTreeMap<usize, OpSet>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(samples: &'a [Sample]) -> Solver<'a> {
+        let mut candidates = BTreeMap::new();
+        for sample in samples {
+            *candidates.entry(sample.inst.op).or_insert(!0) &= matching_ops(sample);
+        }
+        Solver {
+            samples,
+            candidates,
+        }
+    }
+
+    /// Lists the samples which narrowed down the operations of `opcodes`.
+    fn explain(&self, opcodes: &[usize]) -> String {
+        let mut lines = vec![];
+        for &opcode in opcodes {
+            let mut ops = !0;
+            for (i, sample) in self.samples.iter().enumerate() {
+                if sample.inst.op != opcode {
+                    continue;
+                }
+                let matching = matching_ops(sample);
+                if ops & matching != ops {
+                    ops &= matching;
+                    lines.push(format!(
+                        "  sample {} (opcode {}) allows {}",
+                        i + 1,
+                        opcode,
+                        op_names(matching)
+                    ));
+                }
+            }
+        }
+        lines.join("\n")
+    }
+
+    /// Finds the smallest set of opcodes which have fewer operations between
+    /// them than there are opcodes, so that no mapping can exist.
+    fn find_conflict(&self) -> Option<Vec<usize>> {
+        let opcodes: Vec<(usize, OpSet)> = self.candidates.iter().map(|(&k, &v)| (k, v)).collect();
+        let mut subsets: Vec<u32> = (1..1u32 << opcodes.len().min(16)).collect();
+        subsets.sort_by_key(|s| s.count_ones());
+        subsets.into_iter().find_map(|subset| {
+            let chosen: Vec<&(usize, OpSet)> = opcodes
+                .iter()
+                .enumerate()
+                .filter(|&(i, _)| subset & (1 << i) != 0)
+                .map(|(_, c)| c)
+                .collect();
+            let ops = chosen.iter().fold(0, |ops, &&(_, c)| ops | c);
+            if ops.count_ones() < chosen.len() as u32 {
+                Some(chosen.iter().map(|&&(opcode, _)| opcode).collect())
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Assigns the opcodes in `candidates` by elimination, branching on the
+    /// opcode with the fewest choices when elimination stalls.
+    fn search(&self, mut candidates: BTreeMap<usize, OpSet>, found: &mut Vec<OpcodeMap>) {
+        // Remove the operations of opcodes already decided from all others.
+        loop {
+            let decided = candidates
+                .values()
+                .filter(|c| c.count_ones() == 1)
+                .fold(0, |ops, &c| ops | c);
+            let mut changed = false;
+            for c in candidates.values_mut() {
+                if c.count_ones() > 1 && *c & decided != 0 {
+                    *c &= !decided;
+                    changed = true;
+                }
+            }
+            if !changed {
+                break;
+            }
+        }
+        if candidates.values().any(|&c| c == 0) {
+            return;
+        }
+        let open = candidates
+            .iter()
+            .filter(|(_, c)| c.count_ones() > 1)
+            .min_by_key(|(_, c)| c.count_ones());
+        match open {
+            Some((&opcode, &ops)) => {
+                for i in 0..Op::ALL.len() {
+                    if found.len() >= MAX_MAPPINGS {
+                        return;
+                    }
+                    if ops & (1 << i) != 0 {
+                        let mut guess = candidates.clone();
+                        guess.insert(opcode, 1 << i);
+                        self.search(guess, found);
+                    }
+                }
+            }
+            None => {
+                // Two opcodes may still have ended up with the same operation.
+                let all = candidates.values().fold(0, |ops, &c| ops | c);
+                if all.count_ones() as usize == candidates.len() {
+                    found.push(OpcodeMap(
+                        candidates
+                            .iter()
+                            .map(|(&opcode, &c)| (opcode, Op::ALL[c.trailing_zeros() as usize]))
+                            .collect(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+/// Finds the mappings of opcodes to operations consistent with all samples,
+/// at most `MAX_MAPPINGS` of them. Fails with the conflicting samples if
+/// there is none.
+pub fn infer_opcodes(samples: &[Sample]) -> Result<Vec<OpcodeMap>> {
+    let solver = Solver::new(samples);
+    let mut found = vec![];
+    solver.search(solver.candidates.clone(), &mut found);
+    if found.is_empty() {
+        match solver.find_conflict() {
+            Some(opcodes) => {
+                let names: Vec<String> = opcodes.iter().map(|o| o.to_string()).collect();
+                bail!(
+                    "No consistent opcode mapping, opcode(s) {} conflict:\n{}",
+                    names.join(", "),
+                    solver.explain(&opcodes)
+                );
+            }
+            None => bail!("No consistent opcode mapping"),
+        }
+    }
+    Ok(found)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn sample(before: &[usize], inst: [usize; 4], after: &[usize]) -> Sample {
+        Sample {
+            before: before.to_vec(),
+            inst: Encoded {
+                op: inst[0],
+                a: inst[1],
+                b: inst[2],
+                c: inst[3],
+            },
+            after: after.to_vec(),
+        }
+    }
+
+    #[test]
+    fn example() {
+        let s = sample(&[3, 2, 1, 1], [9, 2, 1, 2], &[3, 2, 2, 1]);
+        assert_eq!(op_names(matching_ops(&s)), "addi, mulr, seti");
+    }
+
+    #[test]
+    fn ambiguous() {
+        // Both opcodes could be either addr or borr, so elimination alone
+        // gets nowhere.
+        let samples = [
+            sample(&[1, 2], [5, 0, 1, 1], &[1, 3]),
+            sample(&[1, 2], [6, 0, 1, 1], &[1, 3]),
+        ];
+        let mappings: Vec<String> = infer_opcodes(&samples)
+            .unwrap()
+            .iter()
+            .map(|m| m.to_string())
+            .collect();
+        assert_eq!(mappings, ["5=addr 6=borr", "5=borr 6=addr"]);
+    }
+
+    #[test]
+    fn conflict() {
+        let samples = [
+            sample(&[1, 2], [1, 1, 3, 1], &[1, 5]),
+            sample(&[1, 2], [2, 1, 3, 1], &[1, 5]),
+        ];
+        let err = infer_opcodes(&samples).unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "No consistent opcode mapping, opcode(s) 1, 2 conflict:
+  sample 1 (opcode 1) allows addi
+  sample 2 (opcode 2) allows addi"
+        );
+    }
+}
@@ -0,0 +1,297 @@
+use super::{Inst, Op};
+use std::collections::HashMap;
+
+/// A super-instruction standing in for a whole loop. Executing it has the
+/// same effect on the registers as running the loop, then continues after
+/// its last instruction.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Fused {
+    /// Adds the sum of the divisors of `n` to `sum`, by trying all products
+    /// of the counters `i` and `j`.
+    DivisorSum {
+        n: usize,
+        sum: usize,
+        i: usize,
+        j: usize,
+        tmp: usize,
+        end: usize,
+    },
+    /// Sets `quot` to `n / d` by counting up until `(quot + 1) * d > n`.
+    Divide {
+        n: usize,
+        d: usize,
+        quot: usize,
+        tmp: usize,
+        end: usize,
+    },
+}
+
+impl Fused {
+    /// Executes the loop on `reg` and returns the address to continue at.
+    /// Sums wrap around like those of a `Machine`.
+    pub fn exec(&self, reg: &mut [usize], ip: usize) -> usize {
+        match *self {
+            Fused::DivisorSum {
+                n,
+                sum,
+                i,
+                j,
+                tmp,
+                end,
+            } => {
+                let n = reg[n];
+                let mut total: usize = 0;
+                let mut d = 1;
+                while d <= n / d {
+                    if n.is_multiple_of(d) {
+                        total = total.wrapping_add(d);
+                        if d != n / d {
+                            total = total.wrapping_add(n / d);
+                        }
+                    }
+                    d += 1;
+                }
+                reg[sum] = reg[sum].wrapping_add(total);
+                reg[i] = n.max(1).wrapping_add(1);
+                reg[j] = n.max(1).wrapping_add(1);
+                reg[tmp] = 1;
+                reg[ip] = end - 1;
+                end
+            }
+            Fused::Divide {
+                n,
+                d,
+                quot,
+                tmp,
+                end,
+            } => {
+                reg[quot] = reg[n] / d;
+                reg[tmp] = 1;
+                reg[ip] = end - 1;
+                end
+            }
+        }
+    }
+}
+
+/// Loops the optimiser knows, written as instruction templates. Operands
+/// are `ip` for the bound instruction pointer, `_` for anything, `@k` for
+/// the address of the k-th instruction of the loop, capital letters for
+/// distinct registers and small letters for values.
+const DIVISOR_SUM: &[&str] = &[
+    "seti 1 _ I",
+    "seti 1 _ J",
+    "mulr I J T",
+    "eqrr T N T",
+    "addr T ip ip",
+    "addi ip 1 ip",
+    "addr I S S",
+    "addi J 1 J",
+    "gtrr J N T",
+    "addr T ip ip",
+    "seti @1 _ ip",
+    "addi I 1 I",
+    "gtrr I N T",
+    "addr T ip ip",
+    "seti @0 _ ip",
+];
+
+const DIVIDE: &[&str] = &[
+    "seti 0 _ Q",
+    "addi Q 1 T",
+    "muli T d T",
+    "gtrr T N T",
+    "addr T ip ip",
+    "addi ip 1 ip",
+    "seti @8 _ ip",
+    "addi Q 1 Q",
+    "seti @0 _ ip",
+];
+
+type Bindings = HashMap<char, usize>;
+
+fn is_commutative(op: Op) -> bool {
+    matches!(op, Op::AddR | Op::MulR | Op::BanR | Op::BorR | Op::EqRR)
+}
+
+/// Matches a single operand, binding the variable it names if necessary.
+fn match_operand(pattern: &str, value: usize, ip: usize, start: usize, b: &mut Bindings) -> bool {
+    if pattern == "ip" {
+        return value == ip;
+    }
+    let mut chars = pattern.chars();
+    match (chars.next(), chars.as_str()) {
+        (Some('_'), "") => true,
+        (Some('@'), offset) => offset.parse::<usize>().ok().map(|k| start + k) == Some(value),
+        (Some(var), "") if var.is_ascii_uppercase() => match b.get(&var) {
+            Some(&reg) => reg == value,
+            None => {
+                let taken = b
+                    .iter()
+                    .any(|(v, &reg)| v.is_ascii_uppercase() && reg == value);
+                if taken || value == ip {
+                    return false;
+                }
+                b.insert(var, value);
+                true
+            }
+        },
+        (Some(var), "") if var.is_ascii_lowercase() => *b.entry(var).or_insert(value) == value,
+        _ => pattern.parse::<usize>().ok() == Some(value),
+    }
+}
+
+fn match_inst(pattern: &str, inst: &Inst, ip: usize, start: usize, b: &mut Bindings) -> bool {
+    let words: Vec<&str> = pattern.split_whitespace().collect();
+    if words[0] != inst.op.name() {
+        return false;
+    }
+    let mut orders = vec![(inst.a, inst.b)];
+    if is_commutative(inst.op) {
+        orders.push((inst.b, inst.a));
+    }
+    for (a, b2) in orders {
+        let mut attempt = b.clone();
+        if match_operand(words[1], a, ip, start, &mut attempt)
+            && match_operand(words[2], b2, ip, start, &mut attempt)
+            && match_operand(words[3], inst.c, ip, start, &mut attempt)
+        {
+            *b = attempt;
+            return true;
+        }
+    }
+    false
+}
+
+fn match_idiom(pattern: &[&str], code: &[Inst], ip: usize, start: usize) -> Option<Bindings> {
+    let code = code.get(start..start + pattern.len())?;
+    let mut b = Bindings::new();
+    for (p, inst) in pattern.iter().zip(code) {
+        if !match_inst(p, inst, ip, start, &mut b) {
+            return None;
+        }
+    }
+    Some(b)
+}
+
+/// Finds the loops in `code` that can be replaced by a super-instruction,
+/// keyed by the address of their first instruction.
+pub fn find_idioms(code: &[Inst], ip: Option<usize>) -> Vec<(usize, Fused)> {
+    let ip = match ip {
+        Some(ip) => ip,
+        None => return vec![],
+    };
+    let mut found = vec![];
+    for start in 0..code.len() {
+        if let Some(b) = match_idiom(DIVISOR_SUM, code, ip, start) {
+            found.push((
+                start,
+                Fused::DivisorSum {
+                    n: b[&'N'],
+                    sum: b[&'S'],
+                    i: b[&'I'],
+                    j: b[&'J'],
+                    tmp: b[&'T'],
+                    end: start + DIVISOR_SUM.len(),
+                },
+            ));
+        } else if let Some(b) = match_idiom(DIVIDE, code, ip, start) {
+            // Dividing by zero never terminates, leave that to the loop.
+            if b[&'d'] != 0 {
+                found.push((
+                    start,
+                    Fused::Divide {
+                        n: b[&'N'],
+                        d: b[&'d'],
+                        quot: b[&'Q'],
+                        tmp: b[&'T'],
+                        end: start + DIVIDE.len(),
+                    },
+                ));
+            }
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::{Machine, Overflow, Program, Vm};
+
+    fn run(text: &str, reg: &[usize], optimize: bool) -> Vec<usize> {
+        let program: Program = text.parse().unwrap();
+        let mut machine = Machine::new(&program, reg.len()).unwrap();
+        machine.reg.copy_from_slice(reg);
+        if optimize {
+            assert_eq!(machine.optimize(), 1);
+        }
+        machine.run();
+        machine.reg
+    }
+
+    /// The loop of day 19 with its registers shuffled around.
+    const DIVISOR_SUM_LOOP: &str = "#ip 2
+seti 1 0 3
+seti 1 0 4
+mulr 4 3 1
+eqrr 5 1 1
+addr 2 1 2
+addi 2 1 2
+addr 3 0 0
+addi 4 1 4
+gtrr 4 5 1
+addr 1 2 2
+seti 1 0 2
+addi 3 1 3
+gtrr 3 5 1
+addr 1 2 2
+seti 0 0 2";
+
+    #[test]
+    fn divisor_sum() {
+        let text = DIVISOR_SUM_LOOP;
+        for &n in &[0, 1, 12, 97] {
+            let reg = [5, 0, 0, 0, 0, n];
+            assert_eq!(run(text, &reg, true), run(text, &reg, false));
+        }
+    }
+
+    #[test]
+    fn divide() {
+        let text = "#ip 4
+seti 0 0 5
+addi 5 1 3
+muli 3 7 3
+gtrr 3 2 3
+addr 3 4 4
+addi 4 1 4
+seti 8 2 4
+addi 5 1 5
+seti 0 2 4";
+        for &n in &[0, 6, 7, 100] {
+            let reg = [0, 0, n, 0, 0, 9];
+            assert_eq!(run(text, &reg, true), run(text, &reg, false));
+        }
+    }
+
+    #[test]
+    fn narrow_words() {
+        // Products of the counters wrap around in 8 bits, so the loop finds
+        // more "divisors" than there are.
+        let program: Program = DIVISOR_SUM_LOOP.parse().unwrap();
+        let run = |optimize: bool| {
+            let mut vm: Vm<u8> = Vm::new(&program, 6).unwrap();
+            vm.reg[5] = 200;
+            if optimize {
+                assert_eq!(vm.optimize(), 0);
+            }
+            vm.run();
+            vm.reg
+        };
+        assert_eq!(run(true), run(false));
+
+        let mut machine = Machine::new(&program, 6).unwrap();
+        machine.overflow = Overflow::Checked;
+        assert_eq!(machine.optimize(), 0);
+    }
+}
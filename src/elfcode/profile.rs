@@ -0,0 +1,197 @@
+use super::{Machine, Program};
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::fmt;
+
+/// How many loop entries to keep the registers of.
+const MAX_ENTRIES: usize = 5;
+
+/// The loop closed by the most frequently taken backward jump, which is the
+/// innermost loop doing most of the work.
+pub struct HotLoop {
+    pub start: usize,
+    pub end: usize,
+    pub iterations: u64,
+    pub num_entries: u64,
+    /// Registers on entering the loop and on leaving it again, if it did.
+    pub entries: Vec<(Vec<usize>, Option<Vec<usize>>)>,
+}
+
+pub struct Profile {
+    pub code: Vec<String>,
+    pub counts: Vec<u64>,
+    pub steps: u64,
+    pub halted: bool,
+    pub hot_loop: Option<HotLoop>,
+}
+
+fn start(program: &Program, reg: &[usize]) -> Result<Machine> {
+    let mut machine = Machine::new(program, reg.len())?;
+    machine.reg.copy_from_slice(reg);
+    Ok(machine)
+}
+
+/// Runs the program from the registers `reg` for at most `max_steps`
+/// instructions, counting how often each instruction is executed, and then
+/// again to record the registers around the hot loop.
+pub fn profile(program: &Program, reg: &[usize], max_steps: u64) -> Result<Profile> {
+    let mut machine = start(program, reg)?;
+    let mut counts = vec![0; program.code.len()];
+    let mut back_edges: HashMap<(usize, usize), u64> = HashMap::new();
+    let mut steps = 0;
+    while steps < max_steps {
+        let pc = machine.pc;
+        if !machine.step() {
+            break;
+        }
+        counts[pc] += 1;
+        steps += 1;
+        if machine.pc <= pc {
+            *back_edges.entry((machine.pc, pc)).or_insert(0) += 1;
+        }
+    }
+    let halted = machine.is_halted();
+
+    // Prefer the shorter loop among equally hot ones, as it is nested in the
+    // others.
+    let hottest = back_edges
+        .into_iter()
+        .max_by_key(|&((start, end), count)| (count, usize::MAX - (end - start)));
+    let hot_loop = match hottest {
+        Some(((start, end), iterations)) => Some(HotLoop {
+            start,
+            end,
+            iterations,
+            ..trace_loop(program, reg, steps, start, end)?
+        }),
+        None => None,
+    };
+
+    Ok(Profile {
+        code: program.code.iter().map(|inst| inst.to_string()).collect(),
+        counts,
+        steps,
+        halted,
+        hot_loop,
+    })
+}
+
+/// Runs the program again for `steps` instructions, recording the registers
+/// whenever it enters or leaves the instructions `start..=end`.
+fn trace_loop(
+    program: &Program,
+    reg: &[usize],
+    steps: u64,
+    start_pc: usize,
+    end_pc: usize,
+) -> Result<HotLoop> {
+    let mut machine = start(program, reg)?;
+    let in_loop = |pc: usize| start_pc <= pc && pc <= end_pc;
+    let mut hot_loop = HotLoop {
+        start: start_pc,
+        end: end_pc,
+        iterations: 0,
+        num_entries: 0,
+        entries: vec![],
+    };
+    let mut inside = false;
+    for _ in 0..=steps {
+        let now_inside = in_loop(machine.pc);
+        if now_inside && !inside {
+            hot_loop.num_entries += 1;
+            if hot_loop.entries.len() < MAX_ENTRIES {
+                hot_loop.entries.push((machine.reg.clone(), None));
+            }
+        } else if inside && !now_inside && hot_loop.num_entries <= MAX_ENTRIES as u64 {
+            hot_loop.entries.last_mut().unwrap().1 = Some(machine.reg.clone());
+        }
+        inside = now_inside;
+        if !machine.step() {
+            break;
+        }
+    }
+    Ok(hot_loop)
+}
+
+fn fmt_regs(reg: &[usize]) -> String {
+    let regs: Vec<String> = reg
+        .iter()
+        .enumerate()
+        .map(|(r, v)| format!("r{}={}", r, v))
+        .collect();
+    regs.join(" ")
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let status = if self.halted { "halted" } else { "stopped" };
+        writeln!(f, "Executed {} instructions, {}", self.steps, status)?;
+        let total = self.steps.max(1) as f64;
+        for (pc, (count, inst)) in self.counts.iter().zip(&self.code).enumerate() {
+            let hot = match &self.hot_loop {
+                Some(l) if l.start <= pc && pc <= l.end => "*",
+                _ => " ",
+            };
+            writeln!(
+                f,
+                "{}{:02}  {:<20} {:>12} {:>7.2}%",
+                hot,
+                pc,
+                inst,
+                count,
+                *count as f64 * 100.0 / total
+            )?;
+        }
+        match &self.hot_loop {
+            Some(l) => {
+                writeln!(
+                    f,
+                    "Hot loop: {:02}..={:02}, {} iterations, entered {} times",
+                    l.start, l.end, l.iterations, l.num_entries
+                )?;
+                for (i, (entry, exit)) in l.entries.iter().enumerate() {
+                    writeln!(f, "  entry {}: {}", i + 1, fmt_regs(entry))?;
+                    match exit {
+                        Some(exit) => writeln!(f, "  exit {}:  {}", i + 1, fmt_regs(exit))?,
+                        None => writeln!(f, "  exit {}:  still running", i + 1)?,
+                    }
+                }
+                Ok(())
+            }
+            None => writeln!(f, "No loops"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn nested_loops() {
+        // Three times four increments of r3.
+        let program: Program = "#ip 5
+seti 0 0 1
+seti 0 0 2
+addi 3 1 3
+addi 2 1 2
+gtri 2 3 4
+addr 4 5 5
+seti 1 0 5
+addi 1 1 1
+gtri 1 2 4
+addr 4 5 5
+seti 0 0 5"
+            .parse()
+            .unwrap();
+        let profile = profile(&program, &[0; 6], 1000).unwrap();
+        assert!(profile.halted);
+        assert_eq!(profile.counts[2], 12);
+        let hot_loop = profile.hot_loop.unwrap();
+        assert_eq!((hot_loop.start, hot_loop.end), (2, 6));
+        assert_eq!(hot_loop.iterations, 9);
+        assert_eq!(hot_loop.num_entries, 3);
+        assert_eq!(hot_loop.entries[0].0, [0, 0, 0, 0, 0, 1]);
+        assert_eq!(hot_loop.entries[2].1, Some(vec![0, 2, 4, 12, 1, 6]));
+    }
+}
@@ -0,0 +1,384 @@
+use super::{Machine, Program};
+use crate::prelude::*;
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufWriter, Write};
+use std::path::Path;
+
+const MAGIC: &[u8] = b"ELFT";
+/// How many steps apart the viewer keeps full snapshots of the state.
+const CHECKPOINT_INTERVAL: u64 = 1024;
+
+const HELP: &str = "\
+next [n]       move n steps forward (default 1), also an empty line
+prev [n]       move n steps back (default 1)
+goto <n>       move to step n
+find <i> <n>   move to the n-th time instruction i runs
+end            move past the last step
+quit";
+
+fn write_varint<W: Write>(out: &mut W, mut value: usize) -> io::Result<()> {
+    while value >= 0x80 {
+        out.write_all(&[(value as u8) | 0x80])?;
+        value >>= 7;
+    }
+    out.write_all(&[value as u8])
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> Result<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = match data.get(*pos) {
+            Some(&byte) => byte,
+            None => bail!("Truncated trace"),
+        };
+        *pos += 1;
+        if shift >= usize::BITS {
+            bail!("Invalid number in trace");
+        }
+        value |= ((byte & 0x7f) as usize) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+        shift += 7;
+    }
+}
+
+impl Machine {
+    /// Runs for at most `max_steps` instructions, writing a trace to `path`
+    /// and returning the number of steps. Each step is stored as the address
+    /// of the instruction and the registers it changed.
+    pub fn record<P: AsRef<Path>>(&mut self, path: P, max_steps: u64) -> Result<u64> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let program = Program {
+            ip: self.ip,
+            code: self.code.clone(),
+        };
+        let text = program.to_string();
+        out.write_all(MAGIC)?;
+        write_varint(&mut out, text.len())?;
+        out.write_all(text.as_bytes())?;
+        write_varint(&mut out, self.reg.len())?;
+        for &value in &self.reg {
+            write_varint(&mut out, value)?;
+        }
+        let mut before = self.reg.clone();
+        let mut steps = 0;
+        while steps < max_steps {
+            let pc = self.pc;
+            if !self.step() {
+                break;
+            }
+            // Zero marks the end of the trace.
+            write_varint(&mut out, pc + 1)?;
+            let changed: Vec<usize> = (0..before.len())
+                .filter(|&r| before[r] != self.reg[r])
+                .collect();
+            write_varint(&mut out, changed.len())?;
+            for r in changed {
+                write_varint(&mut out, r)?;
+                write_varint(&mut out, self.reg[r])?;
+                before[r] = self.reg[r];
+            }
+            steps += 1;
+        }
+        write_varint(&mut out, 0)?;
+        write_varint(&mut out, self.pc)?;
+        out.flush()?;
+        Ok(steps)
+    }
+}
+
+/// The state before a step, with how often each instruction ran so far.
+#[derive(Clone)]
+struct State {
+    step: u64,
+    /// Offset of the step in the trace.
+    offset: usize,
+    reg: Vec<usize>,
+    counts: Vec<u64>,
+}
+
+/// A recorded trace, which can be moved through in both directions.
+pub struct Trace {
+    pub program: Program,
+    data: Vec<u8>,
+    checkpoints: Vec<State>,
+    /// The address the machine stopped at after the last step.
+    end_pc: usize,
+    num_steps: u64,
+    state: State,
+}
+
+impl Trace {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Trace> {
+        Trace::from_bytes(fs::read(path)?)
+    }
+
+    fn from_bytes(data: Vec<u8>) -> Result<Trace> {
+        if !data.starts_with(MAGIC) {
+            bail!("Not an Elfcode trace");
+        }
+        let mut pos = MAGIC.len();
+        let len = read_varint(&data, &mut pos)?;
+        let text = data
+            .get(pos..pos + len)
+            .ok_or_else(|| format_err!("Truncated trace"))?;
+        let program: Program = std::str::from_utf8(text)?.parse()?;
+        pos += len;
+        let num_regs = read_varint(&data, &mut pos)?;
+        let reg = (0..num_regs)
+            .map(|_| read_varint(&data, &mut pos))
+            .collect::<Result<Vec<usize>>>()?;
+
+        let mut state = State {
+            step: 0,
+            offset: pos,
+            reg,
+            counts: vec![0; program.code.len()],
+        };
+        let mut trace = Trace {
+            program,
+            data,
+            checkpoints: vec![],
+            end_pc: 0,
+            num_steps: 0,
+            state: state.clone(),
+        };
+        // Check the whole trace once, keeping snapshots along the way.
+        loop {
+            if state.step.is_multiple_of(CHECKPOINT_INTERVAL) {
+                trace.checkpoints.push(state.clone());
+            }
+            if !trace.advance(&mut state)? {
+                break;
+            }
+        }
+        let mut pos = state.offset + 1;
+        trace.end_pc = read_varint(&trace.data, &mut pos)?;
+        trace.num_steps = state.step;
+        Ok(trace)
+    }
+
+    /// Applies the step at `state`, returning false at the end of the trace.
+    fn advance(&self, state: &mut State) -> Result<bool> {
+        let mut pos = state.offset;
+        let pc = match read_varint(&self.data, &mut pos)? {
+            0 => return Ok(false),
+            pc => pc - 1,
+        };
+        let count = state
+            .counts
+            .get_mut(pc)
+            .ok_or_else(|| format_err!("Invalid instruction {} in trace", pc))?;
+        *count += 1;
+        for _ in 0..read_varint(&self.data, &mut pos)? {
+            let r = read_varint(&self.data, &mut pos)?;
+            let value = read_varint(&self.data, &mut pos)?;
+            *state
+                .reg
+                .get_mut(r)
+                .ok_or_else(|| format_err!("Invalid register {} in trace", r))? = value;
+        }
+        state.offset = pos;
+        state.step += 1;
+        Ok(true)
+    }
+
+    #[cfg(test)]
+    pub fn num_steps(&self) -> u64 {
+        self.num_steps
+    }
+
+    pub fn step(&self) -> u64 {
+        self.state.step
+    }
+
+    pub fn reg(&self) -> &[usize] {
+        &self.state.reg
+    }
+
+    /// The address of the instruction the current step executes.
+    pub fn pc(&self) -> usize {
+        self.pc_at(&self.state).unwrap_or(self.end_pc)
+    }
+
+    /// Moves to the state before `step`, or after the last step.
+    pub fn seek(&mut self, step: u64) {
+        let step = step.min(self.num_steps);
+        let checkpoint = (step / CHECKPOINT_INTERVAL) as usize;
+        if step < self.state.step || checkpoint > (self.state.step / CHECKPOINT_INTERVAL) as usize {
+            self.state = self.checkpoints[checkpoint].clone();
+        }
+        let mut state = self.state.clone();
+        while state.step < step {
+            // The trace was checked when opening it.
+            self.advance(&mut state).unwrap();
+        }
+        self.state = state;
+    }
+
+    /// Moves to the step which executes instruction `pc` for the `nth` time,
+    /// counting from 1. Returns false if it doesn't run that often.
+    pub fn find(&mut self, pc: usize, nth: u64) -> bool {
+        if nth == 0 || pc >= self.program.code.len() {
+            return false;
+        }
+        let before = self
+            .checkpoints
+            .partition_point(|c| c.counts[pc] < nth)
+            .saturating_sub(1);
+        let mut state = self.checkpoints[before].clone();
+        loop {
+            if state.counts[pc] == nth - 1 && self.pc_at(&state) == Some(pc) {
+                self.state = state;
+                return true;
+            }
+            if !self.advance(&mut state).unwrap() {
+                return false;
+            }
+        }
+    }
+
+    fn pc_at(&self, state: &State) -> Option<usize> {
+        let mut pos = state.offset;
+        match read_varint(&self.data, &mut pos) {
+            Ok(pc) if pc > 0 => Some(pc - 1),
+            _ => None,
+        }
+    }
+
+    fn current(&self, out: &mut String) {
+        write!(
+            out,
+            "step {}/{}: pc={}",
+            self.step(),
+            self.num_steps,
+            self.pc()
+        )
+        .unwrap();
+        for (r, v) in self.reg().iter().enumerate() {
+            write!(out, " r{}={}", r, v).unwrap();
+        }
+        match self.program.code.get(self.pc()) {
+            Some(inst) if self.step() < self.num_steps => {
+                writeln!(out, "\n=> {:02}  {}", self.pc(), inst).unwrap()
+            }
+            _ => writeln!(out, "\nEnd of trace").unwrap(),
+        }
+    }
+
+    /// Executes one viewer command, writing its output to `out`. Returns
+    /// false once the user asked to quit.
+    pub fn command(&mut self, line: &str, out: &mut String) -> Result<bool> {
+        let mut words = line.split_whitespace();
+        let cmd = words.next().unwrap_or("next");
+        let arg = words.next();
+        let count = || -> Result<u64> { Ok(arg.map_or(Ok(1), str::parse)?) };
+        match cmd {
+            "n" | "next" => self.seek(self.step() + count()?),
+            "p" | "prev" => self.seek(self.step().saturating_sub(count()?)),
+            "g" | "goto" => {
+                let step = arg.ok_or_else(|| format_err!("Missing step"))?.parse()?;
+                self.seek(step);
+            }
+            "f" | "find" => {
+                let pc = arg
+                    .ok_or_else(|| format_err!("Missing instruction index"))?
+                    .parse()?;
+                let nth = words
+                    .next()
+                    .ok_or_else(|| format_err!("Missing count"))?
+                    .parse()?;
+                if !self.find(pc, nth) {
+                    bail!("Instruction {} doesn't run {} times", pc, nth);
+                }
+            }
+            "end" => self.seek(self.num_steps),
+            "q" | "quit" => return Ok(false),
+            "help" => {
+                writeln!(out, "{}", HELP).unwrap();
+                return Ok(true);
+            }
+            _ => bail!("Unknown command '{}', try help", cmd),
+        }
+        self.current(out);
+        Ok(true)
+    }
+}
+
+/// Moves through the trace in `path` interactively, reading commands from
+/// stdin.
+pub fn replay<P: AsRef<Path>>(path: P) -> Result<()> {
+    let mut trace = Trace::open(path)?;
+    let mut out = String::new();
+    trace.current(&mut out);
+    print!("{}", out);
+    let stdin = io::stdin();
+    loop {
+        print!("(trace) ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            break;
+        }
+        out.clear();
+        match trace.command(&line, &mut out) {
+            Ok(true) => print!("{}", out),
+            Ok(false) => break,
+            Err(err) => println!("error: {}", err),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn seek() {
+        // Counts r1 up to 3000, then r2 up to r1.
+        let program: Program = "#ip 5
+seti 0 0 1
+addi 1 1 1
+gtri 1 2999 3
+addr 3 5 5
+seti 0 0 5
+addi 2 1 2
+eqrr 2 1 3
+addr 3 5 5
+seti 4 0 5"
+            .parse()
+            .unwrap();
+        let path = std::env::temp_dir().join(format!("elfcode-{}.trace", std::process::id()));
+        let mut machine = Machine::new(&program, 6).unwrap();
+        let steps = machine.record(&path, 100_000).unwrap();
+        let mut trace = Trace::open(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(trace.num_steps(), steps);
+
+        let state_at = |n: u64| {
+            let mut machine = Machine::new(&program, 6).unwrap();
+            for _ in 0..n {
+                machine.step();
+            }
+            (machine.pc, machine.reg)
+        };
+        for &n in &[5000, 17, 2048, 2047, steps, 0, 9999] {
+            trace.seek(n);
+            assert_eq!((trace.pc(), trace.reg().to_vec()), state_at(n));
+        }
+
+        assert!(trace.find(5, 2500));
+        assert_eq!(trace.reg()[2], 2499);
+        assert_eq!(trace.pc(), 5);
+        assert!(!trace.find(5, 3001));
+
+        let mut out = String::new();
+        assert!(trace.command("prev 2", &mut out).unwrap());
+        assert!(out.ends_with("=> 07  addr 3 5 5\n"), "{}", out);
+        assert!(trace.command("goto x", &mut out).is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::ops::{BitAnd, BitOr};
+
+/// What arithmetic does when a result doesn't fit the register word.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Overflow {
+    Wrapping,
+    /// Stops the machine with an error.
+    Checked,
+    /// Clamps to the largest word. Only the tests use it so far.
+    #[allow(dead_code)]
+    Saturating,
+}
+
+/// An unsigned integer type the registers of a `Vm` can hold. The
+/// arithmetic returns `None` on overflow in `Overflow::Checked` mode.
+pub trait Word:
+    Copy + Default + Eq + Ord + fmt::Debug + fmt::Display + BitAnd<Output = Self> + BitOr<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_usize(value: usize, overflow: Overflow) -> Option<Self>;
+    fn to_usize(self) -> Option<usize>;
+    fn add(self, other: Self, overflow: Overflow) -> Option<Self>;
+    fn mul(self, other: Self, overflow: Overflow) -> Option<Self>;
+}
+
+macro_rules! impl_word {
+    ($($t:ty)*) => {
+        $(
+            impl Word for $t {
+                const ZERO: $t = 0;
+                const ONE: $t = 1;
+
+                fn from_usize(value: usize, overflow: Overflow) -> Option<$t> {
+                    match overflow {
+                        Overflow::Wrapping => Some(value as $t),
+                        Overflow::Checked => <$t>::try_from(value).ok(),
+                        Overflow::Saturating => Some(<$t>::try_from(value).unwrap_or(<$t>::MAX)),
+                    }
+                }
+
+                fn to_usize(self) -> Option<usize> {
+                    usize::try_from(self).ok()
+                }
+
+                fn add(self, other: $t, overflow: Overflow) -> Option<$t> {
+                    match overflow {
+                        Overflow::Wrapping => Some(self.wrapping_add(other)),
+                        Overflow::Checked => self.checked_add(other),
+                        Overflow::Saturating => Some(self.saturating_add(other)),
+                    }
+                }
+
+                fn mul(self, other: $t, overflow: Overflow) -> Option<$t> {
+                    match overflow {
+                        Overflow::Wrapping => Some(self.wrapping_mul(other)),
+                        Overflow::Checked => self.checked_mul(other),
+                        Overflow::Saturating => Some(self.saturating_mul(other)),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_word!(u8 u16 u32 u64 usize);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn overflow() {
+        let sum = |overflow| 200u8.add(100, overflow);
+        assert_eq!(sum(Overflow::Wrapping), Some(44));
+        assert_eq!(sum(Overflow::Checked), None);
+        assert_eq!(sum(Overflow::Saturating), Some(255));
+        assert_eq!(u16::from_usize(70000, Overflow::Wrapping), Some(4464));
+        assert_eq!(u16::from_usize(70000, Overflow::Checked), None);
+        assert_eq!(u16::from_usize(70000, Overflow::Saturating), Some(65535));
+        assert_eq!(3u32.mul(5, Overflow::Checked), Some(15));
+    }
+}
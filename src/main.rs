@@ -5,35 +5,149 @@ use quicli::prelude::*;
 
 mod array2d;
 mod days;
+mod elfcode;
+mod ocr;
+mod output;
 mod prelude;
+mod runner;
+mod verify;
 
 use self::days::DAYS;
-use self::prelude::AocData;
+use self::elfcode::{Machine, Program};
+use self::output::{Format, Output};
+use self::prelude::Part;
+use self::runner::{solve_days, Input};
+use self::verify::{ExpectedAnswers, ANSWERS_PATH};
 
 #[derive(Debug, StructOpt)]
 struct Cli {
     day: Option<usize>,
+    /// Time parsing and solving of each day instead of printing the answers
+    #[structopt(long = "bench")]
+    bench: bool,
+    /// Number of runs per day in bench mode
+    #[structopt(long = "runs", default_value = "10")]
+    runs: usize,
+    /// Compare the answers against data/answers.txt
+    #[structopt(long = "verify")]
+    verify: bool,
+    /// Output format: text, json or tsv
+    #[structopt(long = "format", default_value = "text")]
+    format: Format,
+    /// Number of days to solve in parallel, 0 for one per CPU
+    #[structopt(long = "jobs", short = "j", default_value = "1")]
+    jobs: usize,
+    /// Read the puzzle input from this file instead of data/NN.txt, - for stdin
+    #[structopt(long = "input", short = "i")]
+    input: Option<String>,
+    /// Only solve the given part, 1 or 2
+    #[structopt(long = "part", short = "p")]
+    part: Option<Part>,
+    /// Run the days on their example inputs and check the known answers
+    #[structopt(long = "example")]
+    example: bool,
+    /// Print the day's Elfcode program as annotated pseudo-code
+    #[structopt(long = "disasm")]
+    disasm: bool,
+    /// Print the control-flow graph of the day's Elfcode program in DOT format
+    #[structopt(long = "dot")]
+    dot: bool,
+    /// Step through the day's Elfcode program, reading debugger commands from stdin
+    #[structopt(long = "debug")]
+    debug: bool,
+    /// Count the instructions executed by the day's Elfcode program and find its hot loop
+    #[structopt(long = "profile")]
+    profile: bool,
+    /// Record a trace of the day's Elfcode program to this file
+    #[structopt(long = "record")]
+    record: Option<String>,
+    /// Move through a trace recorded with --record, reading commands from stdin
+    #[structopt(long = "replay")]
+    replay: Option<String>,
+    /// Initial value of register 0 for --debug, --profile and --record
+    #[structopt(long = "r0", default_value = "0")]
+    r0: usize,
+    /// Maximum number of instructions to execute with --profile and --record
+    #[structopt(long = "max-steps", default_value = "100000000")]
+    max_steps: u64,
     #[structopt(flatten)]
     verbosity: Verbosity,
 }
 
-fn run_day(day: usize) -> Result<()> {
-    if day < 1 || day > DAYS.len() {
-        bail!("Day {} out of range", day);
+main!(|args: Cli, log_level: verbosity| {
+    let days: Vec<usize> = match args.day {
+        Some(day) if day < 1 || day > DAYS.len() => bail!("Day {} out of range", day),
+        Some(day) => vec![day],
+        None => (1..=DAYS.len()).collect(),
+    };
+    if args.input.is_some() && args.day.is_none() {
+        bail!("--input requires a day");
     }
-    let data = AocData::new(day);
-    let answers = DAYS[day - 1](&data)?;
-    data.ok()?;
-    println!("Day {}: {}", day, answers);
-    Ok(())
-}
-
-main!(
-    |args: Cli, log_level: verbosity| if let Some(day) = args.day {
-        run_day(day)?;
+    if args.input.is_some() && args.example {
+        bail!("--input and --example can't be combined");
+    }
+    if (args.debug || args.replay.is_some()) && args.input.as_deref() == Some("-") {
+        bail!("--debug and --replay read commands from stdin, so --input can't be -");
+    }
+    let input = Input::from_arg(args.input.as_deref())?;
+    if let Some(path) = &args.replay {
+        elfcode::replay(path)?;
+    } else if args.disasm || args.dot || args.debug || args.profile || args.record.is_some() {
+        let day = match args.day {
+            Some(day) => day,
+            None => bail!("--disasm, --dot, --debug, --profile and --record require a day"),
+        };
+        let program = Program::load(input.data(day))?;
+        let mut reg = [0; 6];
+        reg[0] = args.r0;
+        if args.debug {
+            elfcode::debug(&program, &reg)?;
+        }
+        if args.profile {
+            print!("{}", elfcode::profile(&program, &reg, args.max_steps)?);
+        }
+        if let Some(path) = &args.record {
+            let mut machine = Machine::new(&program, reg.len())?;
+            machine.reg.copy_from_slice(&reg);
+            let steps = machine.record(path, args.max_steps)?;
+            println!("Recorded {} steps to {}", steps, path);
+        }
+        if args.disasm {
+            print!("{}", elfcode::disassemble(&program));
+        }
+        if args.dot {
+            print!("{}", elfcode::to_dot(&program));
+        }
+    } else if args.example {
+        runner::examples(&days, args.part)?;
+    } else if args.bench {
+        runner::bench(&days, &input, args.part, args.runs)?;
     } else {
-        for day in 1..=DAYS.len() {
-            run_day(day)?;
+        let expected = if args.verify {
+            Some(ExpectedAnswers::load(ANSWERS_PATH)?)
+        } else {
+            None
+        };
+        let mut output = Output::new(args.format);
+        let mut failed = vec![];
+        output.begin();
+        solve_days(&days, &input, args.part, args.jobs, |day, result| {
+            let mut ok = result.is_ok();
+            if let (Ok((answers, _)), Some(expected)) = (&result, &expected) {
+                let diffs = expected.check(day, answers, args.part);
+                for diff in &diffs {
+                    eprintln!("Day {}: mismatch in {}", day, diff);
+                }
+                ok &= diffs.is_empty();
+            }
+            output.record(day, &result);
+            if !ok {
+                failed.push(day.to_string());
+            }
+        })?;
+        output.end();
+        if !failed.is_empty() {
+            bail!("Failed day(s): {}", failed.join(", "));
         }
     }
-);
+});
@@ -0,0 +1,196 @@
+use crate::array2d::Array2d;
+use crate::prelude::*;
+
+const GLYPH_WIDTH: usize = 6;
+const GLYPH_HEIGHT: usize = 10;
+
+/// The 6x10 font used for the messages in the sky.
+const GLYPHS: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    (
+        'A',
+        [
+            "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'B',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+            "#....#", "#####.",
+        ],
+    ),
+    (
+        'C',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#....#", ".####.",
+        ],
+    ),
+    (
+        'E',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'F',
+        [
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'G',
+        [
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+            "#...##", ".###.#",
+        ],
+    ),
+    (
+        'H',
+        [
+            "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'J',
+        [
+            "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+            "#...#.", ".###..",
+        ],
+    ),
+    (
+        'K',
+        [
+            "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+            "#...#.", "#....#",
+        ],
+    ),
+    (
+        'L',
+        [
+            "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'N',
+        [
+            "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+            "#...##", "#....#",
+        ],
+    ),
+    (
+        'P',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'R',
+        [
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'X',
+        [
+            "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'Z',
+        [
+            "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+];
+
+/// Reads the capital letters spelled by the set pixels of `image`. Empty
+/// rows and columns around the text are ignored and letters are separated
+/// at empty columns, so the image doesn't need to be cropped exactly.
+pub fn recognize(image: &Array2d<bool>) -> Result<String> {
+    let (width, height) = (image.width(), image.height());
+    let row_set = |y: usize| (0..width).any(|x| image[(x, y)]);
+    let column_set = |x: usize, y0: usize| (y0..y0 + GLYPH_HEIGHT).any(|y| image[(x, y)]);
+
+    let y0 = match (0..height).find(|&y| row_set(y)) {
+        Some(y) => y,
+        None => bail!("No text found"),
+    };
+    let y1 = (0..height).rev().find(|&y| row_set(y)).unwrap() + 1;
+    if y1 - y0 != GLYPH_HEIGHT {
+        bail!("Text is {} pixels high, expected {}", y1 - y0, GLYPH_HEIGHT);
+    }
+
+    let mut text = String::new();
+    let mut x = 0;
+    while x < width {
+        if !column_set(x, y0) {
+            x += 1;
+            continue;
+        }
+        let x0 = x;
+        while x < width && column_set(x, y0) {
+            x += 1;
+        }
+        if x - x0 > GLYPH_WIDTH {
+            bail!("Glyph at column {} is too wide", x0);
+        }
+        let pixel = |gx: usize, gy: usize| x0 + gx < x && image[(x0 + gx, y0 + gy)];
+        let letter = GLYPHS.iter().find(|(_, rows)| {
+            rows.iter().enumerate().all(|(gy, row)| {
+                row.chars()
+                    .enumerate()
+                    .all(|(gx, c)| (c == '#') == pixel(gx, gy))
+            })
+        });
+        match letter {
+            Some(&(c, _)) => text.push(c),
+            None => bail!("Unknown glyph at column {}", x0),
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn render(text: &str, border: usize) -> Array2d<bool> {
+        let width = text.len() * (GLYPH_WIDTH + 2) + border * 2;
+        Array2d::new_with(width, GLYPH_HEIGHT + border * 2, |x, y| {
+            if x < border || y < border || y >= GLYPH_HEIGHT + border {
+                return false;
+            }
+            let (i, gx) = ((x - border) / 8, (x - border) % 8);
+            let rows = match text.chars().nth(i) {
+                Some(c) => &GLYPHS.iter().find(|g| g.0 == c).unwrap().1,
+                None => return false,
+            };
+            rows[y - border].as_bytes().get(gx) == Some(&b'#')
+        })
+    }
+
+    #[test]
+    fn round_trip() {
+        let all: String = GLYPHS.iter().map(|g| g.0).collect();
+        assert_eq!(recognize(&render(&all, 0)).unwrap(), all);
+        assert_eq!(recognize(&render("BFFZCNXE", 3)).unwrap(), "BFFZCNXE");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(recognize(&Array2d::new(5, 5)).is_err());
+        let mut image = render("HX", 0);
+        image.put(0, 0, false);
+        assert!(recognize(&image).is_err());
+    }
+}
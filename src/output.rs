@@ -0,0 +1,210 @@
+use crate::prelude::*;
+use crate::runner::Timing;
+use std::str::FromStr;
+
+#[derive(Clone, Copy, Debug)]
+pub enum Format {
+    Text,
+    Json,
+    Tsv,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format> {
+        match s {
+            "text" => Ok(Format::Text),
+            "json" => Ok(Format::Json),
+            "tsv" => Ok(Format::Tsv),
+            _ => bail!("Unknown format '{}', expected text, json or tsv", s),
+        }
+    }
+}
+
+/// Prints one record per day in the selected format.
+pub struct Output {
+    format: Format,
+    num_records: usize,
+}
+
+impl Output {
+    pub fn new(format: Format) -> Output {
+        Output {
+            format,
+            num_records: 0,
+        }
+    }
+
+    pub fn begin(&self) {
+        match self.format {
+            Format::Text => (),
+            Format::Json => println!("["),
+            Format::Tsv => println!("day\tfirst\tsecond\tparse_ms\tsolve_ms\terror"),
+        }
+    }
+
+    pub fn record(&mut self, day: usize, result: &Result<(Answers, Timing)>) {
+        let record = format_record(self.format, day, result);
+        match self.format {
+            Format::Text | Format::Tsv => println!("{}", record),
+            Format::Json => {
+                if self.num_records > 0 {
+                    println!(",");
+                }
+                print!("{}", record);
+            }
+        }
+        self.num_records += 1;
+    }
+
+    pub fn end(&self) {
+        if let Format::Json = self.format {
+            if self.num_records > 0 {
+                println!();
+            }
+            println!("]");
+        }
+    }
+}
+
+fn format_record(format: Format, day: usize, result: &Result<(Answers, Timing)>) -> String {
+    match format {
+        Format::Text => match result {
+            Ok((answers, _)) => format!("Day {}: {}", day, answers),
+            Err(err) => format!("Day {}: error: {}", day, err),
+        },
+        Format::Json => {
+            let (first, second, parse, solve, error) = fields(result);
+            format!(
+                "{{\"day\": {}, \"first\": {}, \"second\": {}, \"parse_ms\": {}, \"solve_ms\": {}, \"error\": {}}}",
+                day,
+                json_opt_answer(first),
+                json_opt_answer(second),
+                json_opt_ms(parse),
+                json_opt_ms(solve),
+                json_opt_str(error.as_deref())
+            )
+        }
+        Format::Tsv => {
+            let (first, second, parse, solve, error) = fields(result);
+            format!(
+                "{}\t{}\t{}\t{}\t{}\t{}",
+                day,
+                tsv_escape(first.map_or("", Answer::as_str)),
+                tsv_escape(second.map_or("", Answer::as_str)),
+                parse.map(ms).unwrap_or_default(),
+                solve.map(ms).unwrap_or_default(),
+                tsv_escape(error.as_deref().unwrap_or(""))
+            )
+        }
+    }
+}
+
+type Fields<'a> = (
+    Option<&'a Answer>,
+    Option<&'a Answer>,
+    Option<f64>,
+    Option<f64>,
+    Option<String>,
+);
+
+fn fields(result: &Result<(Answers, Timing)>) -> Fields<'_> {
+    match result {
+        Ok((answers, timing)) => (
+            answers.first(),
+            answers.second(),
+            Some(timing.parse.as_secs_f64()),
+            Some(timing.solve.as_secs_f64()),
+            None,
+        ),
+        Err(err) => (None, None, None, None, Some(err.to_string())),
+    }
+}
+
+fn ms(secs: f64) -> String {
+    format!("{:.3}", secs * 1000.0)
+}
+
+fn json_opt_ms(secs: Option<f64>) -> String {
+    secs.map(ms).unwrap_or_else(|| "null".into())
+}
+
+/// Grid answers are written as an array of rows.
+fn json_opt_answer(answer: Option<&Answer>) -> String {
+    match answer {
+        Some(Answer::Grid(s)) => {
+            let rows: Vec<String> = s.lines().map(json_str).collect();
+            format!("[{}]", rows.join(", "))
+        }
+        Some(answer) => json_str(answer.as_str()),
+        None => "null".into(),
+    }
+}
+
+fn json_opt_str(s: Option<&str>) -> String {
+    match s {
+        Some(s) => json_str(s),
+        None => "null".into(),
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+fn tsv_escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn escaping() {
+        let timing = Timing {
+            parse: Duration::from_micros(1500),
+            solve: Duration::from_millis(2),
+        };
+        let result = Ok((
+            answers("a\"b\\c\nd\te", Answer::grid("#.\n.#")).unwrap(),
+            timing,
+        ));
+        assert_eq!(
+            format_record(Format::Json, 3, &result),
+            r##"{"day": 3, "first": "a\"b\\c\nd\te", "second": ["#.", ".#"], "parse_ms": 1.500, "solve_ms": 2.000, "error": null}"##
+        );
+        assert_eq!(
+            format_record(Format::Tsv, 3, &result),
+            "3\ta\"b\\\\c\\nd\\te\t#.\\n.#\t1.500\t2.000\t"
+        );
+
+        let result = Err(format_err!("bad\tinput"));
+        assert_eq!(
+            format_record(Format::Json, 4, &result),
+            r#"{"day": 4, "first": null, "second": null, "parse_ms": null, "solve_ms": null, "error": "bad\tinput"}"#
+        );
+        assert_eq!(
+            format_record(Format::Tsv, 4, &result),
+            "4\t\t\t\t\tbad\\tinput"
+        );
+    }
+}
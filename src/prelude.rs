@@ -1,182 +1,635 @@
 #![allow(dead_code)]
 
+use crate::array2d::Array2d;
 pub use quicli::prelude::*;
-use std::cell::Cell;
+use regex::Captures;
+use std::cell::{Cell, RefCell};
 use std::fmt;
 use std::fs::File;
 use std::io::prelude::*;
 use std::io::{BufReader, Cursor};
 use std::marker::PhantomData;
+use std::mem;
 use std::path::PathBuf;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
 
 pub use regex::Regex;
 
-pub struct Answer(String);
+pub enum Answer {
+    Value(String),
+    /// A multi-line answer like a picture drawn in characters, with rows
+    /// separated by `'\n'`.
+    Grid(String),
+}
 
 impl From<usize> for Answer {
     fn from(v: usize) -> Answer {
-        Answer(v.to_string())
+        Answer::Value(v.to_string())
     }
 }
 
 impl From<isize> for Answer {
     fn from(v: isize) -> Answer {
-        Answer(v.to_string())
+        Answer::Value(v.to_string())
     }
 }
 
 impl From<i32> for Answer {
     fn from(v: i32) -> Answer {
-        Answer(v.to_string())
+        Answer::Value(v.to_string())
     }
 }
 
 impl From<u32> for Answer {
     fn from(v: u32) -> Answer {
-        Answer(v.to_string())
+        Answer::Value(v.to_string())
     }
 }
 
 impl From<u8> for Answer {
     fn from(v: u8) -> Answer {
-        Answer(v.to_string())
+        Answer::Value(v.to_string())
+    }
+}
+
+impl From<i64> for Answer {
+    fn from(v: i64) -> Answer {
+        Answer::Value(v.to_string())
+    }
+}
+
+impl From<u64> for Answer {
+    fn from(v: u64) -> Answer {
+        Answer::Value(v.to_string())
+    }
+}
+
+impl From<u128> for Answer {
+    fn from(v: u128) -> Answer {
+        Answer::Value(v.to_string())
     }
 }
 
 impl From<String> for Answer {
     fn from(v: String) -> Answer {
-        Answer(v)
+        Answer::Value(v)
     }
 }
 
-impl<A: Into<Answer>, B: Into<Answer>> From<(A, B)> for Answer {
-    fn from(v: (A, B)) -> Answer {
-        Answer(format!("({}, {})", v.0.into(), v.1.into()))
+impl<'a> From<&'a str> for Answer {
+    fn from(v: &'a str) -> Answer {
+        Answer::Value(v.to_string())
     }
 }
 
-impl<A: Into<Answer>, B: Into<Answer>, C: Into<Answer>> From<(A, B, C)> for Answer {
-    fn from(v: (A, B, C)) -> Answer {
-        Answer(format!("({}, {}, {})", v.0.into(), v.1.into(), v.2.into()))
+macro_rules! impl_answer_from_tuple {
+    ($($t:ident $i:tt),*) => {
+        impl<$($t: Into<Answer>),*> From<($($t,)*)> for Answer {
+            fn from(v: ($($t,)*)) -> Answer {
+                let parts: Vec<String> = vec![$(v.$i.into().to_string()),*];
+                Answer::Value(format!("({})", parts.join(", ")))
+            }
+        }
+    };
+}
+
+impl_answer_from_tuple!(A 0, B 1);
+impl_answer_from_tuple!(A 0, B 1, C 2);
+impl_answer_from_tuple!(A 0, B 1, C 2, D 3);
+impl_answer_from_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_answer_from_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+
+impl Answer {
+    /// Creates a multi-line answer, dropping empty lines around it.
+    pub fn grid<S: AsRef<str>>(s: S) -> Answer {
+        Answer::Grid(s.as_ref().trim_matches('\n').to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Answer::Value(s) | Answer::Grid(s) => s,
+        }
+    }
+
+    pub fn is_grid(&self) -> bool {
+        match self {
+            Answer::Value(_) => false,
+            Answer::Grid(_) => true,
+        }
+    }
+
+    fn write_labeled(&self, f: &mut fmt::Formatter, label: &str) -> fmt::Result {
+        match self {
+            Answer::Value(s) => write!(f, "{}: {}", label, s),
+            Answer::Grid(s) => {
+                write!(f, "{}:", label)?;
+                for row in s.lines() {
+                    write!(f, "\n    {}", row)?;
+                }
+                Ok(())
+            }
+        }
     }
 }
 
 impl fmt::Display for Answer {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0)
+        write!(f, "{}", self.as_str())
     }
 }
 
-pub struct Answers(Answer, Option<Answer>);
+pub struct Answers(Option<Answer>, Option<Answer>);
+
+impl Answers {
+    pub fn first(&self) -> Option<&Answer> {
+        self.0.as_ref()
+    }
+
+    pub fn second(&self) -> Option<&Answer> {
+        self.1.as_ref()
+    }
+
+    /// Drops the answer to the part that wasn't asked for.
+    pub fn select(self, part: Option<Part>) -> Answers {
+        match part {
+            None => self,
+            Some(Part::First) => Answers(self.0, None),
+            Some(Part::Second) => Answers(None, self.1),
+        }
+    }
+}
 
 impl fmt::Display for Answers {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "First answer: {}", self.0)?;
-        if let Some(ref bonus) = self.1 {
-            write!(f, ", second answer: {}", bonus)?;
+        if self.0.iter().chain(self.1.iter()).any(Answer::is_grid) {
+            // Multi-line answers get one line per label.
+            if let Some(ref first) = self.0 {
+                first.write_labeled(f, "First answer")?;
+                if self.1.is_some() {
+                    writeln!(f)?;
+                }
+            }
+            if let Some(ref second) = self.1 {
+                second.write_labeled(f, "Second answer")?;
+            }
+            return Ok(());
+        }
+        match (&self.0, &self.1) {
+            (Some(first), Some(second)) => {
+                write!(f, "First answer: {}, second answer: {}", first, second)
+            }
+            (Some(first), None) => write!(f, "First answer: {}", first),
+            (None, Some(second)) => write!(f, "Second answer: {}", second),
+            (None, None) => write!(f, "No answers"),
         }
-        Ok(())
     }
 }
 
 pub type AocResult = Result<Answers>;
 
 pub fn answer<T: Into<Answer>>(v: T) -> AocResult {
-    Ok(Answers(v.into(), None))
+    Ok(Answers(Some(v.into()), None))
 }
 
 pub fn answers<A: Into<Answer>, B: Into<Answer>>(a: A, b: B) -> AocResult {
-    Ok(Answers(a.into(), Some(b.into())))
+    Ok(Answers(Some(a.into()), Some(b.into())))
+}
+
+/// Like `answers`, but only computes the parts requested from `data`.
+pub fn parts<A, B, FA, FB>(data: &AocData, first: FA, second: FB) -> AocResult
+where
+    A: Into<Answer>,
+    B: Into<Answer>,
+    FA: FnOnce() -> Result<A>,
+    FB: FnOnce() -> Result<B>,
+{
+    let first = if data.wants(Part::First) {
+        Some(first()?.into())
+    } else {
+        None
+    };
+    let second = if data.wants(Part::Second) {
+        Some(second()?.into())
+    } else {
+        None
+    };
+    Ok(Answers(first, second))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Part {
+    First,
+    Second,
+}
+
+impl FromStr for Part {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Part> {
+        match s {
+            "1" => Ok(Part::First),
+            "2" => Ok(Part::Second),
+            _ => bail!("Unknown part '{}', expected 1 or 2", s),
+        }
+    }
+}
+
+/// A sample input from the puzzle text together with the answers it is
+/// known to produce.
+pub struct Example {
+    pub input: &'static str,
+    pub first: Option<&'static str>,
+    pub second: Option<&'static str>,
+}
+
+impl Example {
+    /// Whether an answer is known for `part`, `None` meaning either part.
+    pub fn covers(&self, part: Option<Part>) -> bool {
+        match part {
+            None => self.first.is_some() || self.second.is_some(),
+            Some(Part::First) => self.first.is_some(),
+            Some(Part::Second) => self.second.is_some(),
+        }
+    }
 }
 
 enum DataSrc {
     Path(PathBuf),
     Str(&'static str),
+    String(String),
 }
 
 pub struct AocData {
     data: DataSrc,
-    error: Cell<Option<Error>>,
+    part: Option<Part>,
+    errors: RefCell<Vec<Error>>,
+    parse_time: Cell<Duration>,
 }
 
 impl AocData {
     pub fn new(day: usize) -> AocData {
-        AocData {
-            data: DataSrc::Path(format!("data/{:02}.txt", day).into()),
-            error: Cell::new(None),
-        }
+        AocData::from_path(format!("data/{:02}.txt", day))
+    }
+
+    pub fn from_path<P: Into<PathBuf>>(path: P) -> AocData {
+        AocData::with_src(DataSrc::Path(path.into()))
     }
 
     pub fn from_str(s: &'static str) -> AocData {
+        AocData::with_src(DataSrc::Str(s.trim()))
+    }
+
+    /// Takes input read at runtime, like a file without the trailing
+    /// newlines. Leading whitespace is kept, since it can be part of a map.
+    pub fn from_string(mut s: String) -> AocData {
+        s.truncate(s.trim_end().len());
+        AocData::with_src(DataSrc::String(s))
+    }
+
+    fn with_src(data: DataSrc) -> AocData {
         AocData {
-            data: DataSrc::Str(s.trim()),
-            error: Cell::new(None),
+            data,
+            part: None,
+            errors: RefCell::new(Vec::new()),
+            parse_time: Cell::new(Duration::default()),
         }
     }
 
+    /// Restricts the solution to a single part, `None` meaning both.
+    pub fn with_part(mut self, part: Option<Part>) -> AocData {
+        self.part = part;
+        self
+    }
+
+    pub fn wants(&self, part: Part) -> bool {
+        self.part.is_none() || self.part == Some(part)
+    }
+
     pub fn to_string(&self) -> Result<String> {
+        let start = Instant::now();
         let mut s = match self.data {
             DataSrc::Path(ref path) => read_file(path)?,
             DataSrc::Str(s) => s.to_string(),
+            DataSrc::String(ref s) => s.clone(),
         };
         s.truncate(s.trim_end().len());
+        self.add_parse_time(start.elapsed());
         Ok(s)
     }
 
-    pub fn lines(&self) -> Result<AocLines<String>> {
+    pub fn lines(&self) -> Result<AocLines<'_, String>> {
         self.values()
     }
 
-    pub fn values<T: FromStr>(&self) -> Result<AocLines<T>> {
+    /// Splits the input into blocks of lines separated by empty lines.
+    pub fn sections(&self) -> Result<Vec<Vec<String>>> {
+        let mut sections = vec![];
+        let mut section = vec![];
+        for line in self.lines()? {
+            if line.is_empty() {
+                if !section.is_empty() {
+                    sections.push(mem::take(&mut section));
+                }
+            } else {
+                section.push(line);
+            }
+        }
+        if !section.is_empty() {
+            sections.push(section);
+        }
+        Ok(sections)
+    }
+
+    /// Parses the input as a character grid. Lines shorter than the longest
+    /// one are padded with `T::default()`.
+    pub fn grid<T, F>(&self, mut f: F) -> Result<Array2d<T>>
+    where
+        T: Default,
+        F: FnMut(char) -> Result<T>,
+    {
+        let lines: Vec<String> = self.lines()?.collect();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut grid = Array2d::new_with(width, lines.len(), |_, _| T::default());
+        for (y, line) in lines.iter().enumerate() {
+            for (x, c) in line.chars().enumerate() {
+                grid[(x, y)] = f(c).map_err(|err| {
+                    format_err!("{}:{}:{}: {}", self.source_name(), y + 1, x + 1, err)
+                })?;
+            }
+        }
+        Ok(grid)
+    }
+
+    /// Matches each line against `re` and parses the capture groups into the
+    /// tuple `T`. Lines that don't match or fail to parse are skipped and
+    /// their errors collected like in `values`.
+    pub fn records<T: FromCaptures>(&self, re: &str) -> Result<AocRecords<'_, T>> {
+        Ok(AocRecords {
+            lines: self.lines()?,
+            re: Regex::new(re)?,
+            t: PhantomData,
+        })
+    }
+
+    /// Parses each line of the input. Lines that fail to parse are skipped
+    /// and their errors collected, see `errors` and `ok`.
+    pub fn values<T: FromStr>(&self) -> Result<AocLines<'_, T>>
+    where
+        T::Err: fmt::Display,
+    {
         Ok(AocLines {
             file: match self.data {
                 DataSrc::Path(ref path) => Box::new(BufReader::new(File::open(path)?)),
                 DataSrc::Str(s) => Box::new(Cursor::new(s)),
+                DataSrc::String(ref s) => Box::new(Cursor::new(s.as_str())),
             },
-            error: &self.error,
+            source: self.source_name(),
+            line: 0,
+            errors: &self.errors,
+            parse_time: &self.parse_time,
             buffer: String::new(),
             t: PhantomData,
         })
     }
 
+    fn source_name(&self) -> String {
+        match self.data {
+            DataSrc::Path(ref path) => path.display().to_string(),
+            DataSrc::Str(_) | DataSrc::String(_) => "<input>".to_string(),
+        }
+    }
+
+    /// The messages of all errors encountered while reading the input so far.
+    pub fn errors(&self) -> Vec<String> {
+        self.errors.borrow().iter().map(|e| e.to_string()).collect()
+    }
+
+    /// Total time spent reading and parsing the input so far.
+    pub fn parse_time(&self) -> Duration {
+        self.parse_time.get()
+    }
+
+    fn add_parse_time(&self, d: Duration) {
+        self.parse_time.set(self.parse_time.get() + d);
+    }
+
     pub fn ok(self) -> Result<()> {
-        if let Some(err) = self.error.into_inner() {
-            return Err(err);
+        let mut errors = self.errors.into_inner();
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            n => {
+                let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                bail!("{} input errors:\n{}", n, messages.join("\n"))
+            }
         }
-        Ok(())
     }
 }
 
+/// Iterator over the parsed lines of an `AocData`. A line that fails to
+/// parse doesn't end the iteration, it is skipped and its error recorded in
+/// the `AocData` instead.
 pub struct AocLines<'a, T: FromStr> {
-    file: Box<dyn BufRead>,
-    error: &'a Cell<Option<Error>>,
+    file: Box<dyn BufRead + 'a>,
+    source: String,
+    line: usize,
+    errors: &'a RefCell<Vec<Error>>,
+    parse_time: &'a Cell<Duration>,
     buffer: String,
     t: PhantomData<T>,
 }
 
-impl<'a, T: FromStr> Iterator for AocLines<'a, T> {
+impl<'a, T: FromStr> Iterator for AocLines<'a, T>
+where
+    T::Err: fmt::Display,
+{
     type Item = T;
 
     fn next(&mut self) -> Option<T> {
-        self.buffer.clear();
-        match self.file.read_line(&mut self.buffer) {
-            Ok(0) => None,
-            Ok(_) => {
-                let trimmed = self.buffer.trim_end();
-                match trimmed.parse() {
-                    Ok(r) => Some(r),
-                    Err(_) => {
-                        self.error
-                            .set(Some(format_err!("Failed to parse '{}'", trimmed)));
-                        None
+        let start = Instant::now();
+        let result = self.read_next();
+        self.parse_time.set(self.parse_time.get() + start.elapsed());
+        result
+    }
+}
+
+impl<'a, T: FromStr> AocLines<'a, T>
+where
+    T::Err: fmt::Display,
+{
+    fn read_next(&mut self) -> Option<T> {
+        loop {
+            self.buffer.clear();
+            self.line += 1;
+            match self.file.read_line(&mut self.buffer) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    let trimmed = self.buffer.trim_end();
+                    match trimmed.parse() {
+                        Ok(r) => return Some(r),
+                        Err(err) => self.errors.borrow_mut().push(format_err!(
+                            "{}:{}: Failed to parse '{}': {}",
+                            self.source,
+                            self.line,
+                            trimmed,
+                            err
+                        )),
                     }
                 }
+                Err(err) => {
+                    self.errors.borrow_mut().push(format_err!(
+                        "{}:{}: {}",
+                        self.source,
+                        self.line,
+                        err
+                    ));
+                    return None;
+                }
             }
-            Err(err) => {
-                self.error.set(Some(err.into()));
-                None
+        }
+    }
+}
+
+pub struct AocRecords<'a, T: FromCaptures> {
+    lines: AocLines<'a, String>,
+    re: Regex,
+    t: PhantomData<T>,
+}
+
+impl<'a, T: FromCaptures> Iterator for AocRecords<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        loop {
+            let line = self.lines.next()?;
+            let start = Instant::now();
+            let result = match self.re.captures(&line) {
+                Some(caps) => T::from_captures(&caps),
+                None => Err(format_err!("Doesn't match '{}'", self.re)),
+            };
+            let lines = &self.lines;
+            lines
+                .parse_time
+                .set(lines.parse_time.get() + start.elapsed());
+            match result {
+                Ok(record) => return Some(record),
+                Err(err) => lines.errors.borrow_mut().push(format_err!(
+                    "{}:{}: Failed to parse '{}': {}",
+                    lines.source,
+                    lines.line,
+                    line,
+                    err
+                )),
             }
         }
     }
 }
+
+/// Conversion of regex capture groups into a tuple, parsing group `i + 1`
+/// into the `i`-th element.
+pub trait FromCaptures: Sized {
+    fn from_captures(caps: &Captures) -> Result<Self>;
+}
+
+fn parse_capture<T: FromStr>(caps: &Captures, i: usize) -> Result<T>
+where
+    T::Err: fmt::Display,
+{
+    let s = caps
+        .get(i)
+        .ok_or_else(|| format_err!("Capture group {} missing", i))?
+        .as_str();
+    s.parse()
+        .map_err(|err| format_err!("Capture group {} ('{}'): {}", i, s, err))
+}
+
+macro_rules! impl_from_captures {
+    ($($t:ident $i:expr),*) => {
+        impl<$($t: FromStr),*> FromCaptures for ($($t,)*)
+        where
+            $($t::Err: fmt::Display),*
+        {
+            fn from_captures(caps: &Captures) -> Result<Self> {
+                Ok(($(parse_capture::<$t>(caps, $i)?,)*))
+            }
+        }
+    };
+}
+
+impl_from_captures!(A 1);
+impl_from_captures!(A 1, B 2);
+impl_from_captures!(A 1, B 2, C 3);
+impl_from_captures!(A 1, B 2, C 3, D 4);
+impl_from_captures!(A 1, B 2, C 3, D 4, E 5);
+impl_from_captures!(A 1, B 2, C 3, D 4, E 5, F 6);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn values() {
+        let data = AocData::from_str("1\nx\n3\ny");
+        let values: Vec<i32> = data.values().unwrap().collect();
+        assert_eq!(values, [1, 3]);
+        assert_eq!(
+            data.errors(),
+            [
+                "<input>:2: Failed to parse 'x': invalid digit found in string",
+                "<input>:4: Failed to parse 'y': invalid digit found in string",
+            ]
+        );
+        assert_eq!(
+            data.ok().unwrap_err().to_string(),
+            "2 input errors:\n\
+             <input>:2: Failed to parse 'x': invalid digit found in string\n\
+             <input>:4: Failed to parse 'y': invalid digit found in string"
+        );
+    }
+
+    #[test]
+    fn sections() {
+        let data = AocData::from_str("a\n\n\nb\nc\n\n\n\nd");
+        assert_eq!(
+            data.sections().unwrap(),
+            [vec!["a"], vec!["b", "c"], vec!["d"]]
+        );
+    }
+
+    #[test]
+    fn grid() {
+        let data = AocData::from_str("ab\nc");
+        let grid = data.grid(|c| Ok(Some(c))).unwrap();
+        assert_eq!((grid.width(), grid.height()), (2, 2));
+        assert_eq!(grid[(1, 0)], Some('b'));
+        assert_eq!(grid[(0, 1)], Some('c'));
+        assert_eq!(grid[(1, 1)], None);
+
+        let data = AocData::from_str("ab\ncx");
+        let grid = data.grid(|c| match c {
+            'x' => bail!("Bad tile"),
+            c => Ok(c),
+        });
+        match grid {
+            Ok(_) => panic!("Expected an error"),
+            Err(err) => assert_eq!(err.to_string(), "<input>:2:2: Bad tile"),
+        }
+    }
+
+    #[test]
+    fn records() {
+        let data = AocData::from_str("1 2\nfoo\n3 x");
+        let re = r"^(\d+) (\w+)$";
+        let records: Vec<(i32, i32)> = data.records(re).unwrap().collect();
+        assert_eq!(records, [(1, 2)]);
+        assert_eq!(
+            data.errors(),
+            [
+                r"<input>:2: Failed to parse 'foo': Doesn't match '^(\d+) (\w+)$'",
+                "<input>:3: Failed to parse '3 x': Capture group 2 ('x'): \
+                 invalid digit found in string",
+            ]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use crate::days::DAYS;
+use crate::prelude::*;
+use crate::verify::check_example;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::io::{self, Read};
+use std::panic;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, Default)]
+pub struct Timing {
+    pub parse: Duration,
+    pub solve: Duration,
+}
+
+impl Timing {
+    pub fn total(&self) -> Duration {
+        self.parse + self.solve
+    }
+}
+
+/// Where to read the puzzle input from.
+#[derive(Clone)]
+pub enum Input {
+    Default,
+    Path(PathBuf),
+    Text(String),
+}
+
+impl Input {
+    /// Interprets a `--input` argument, `-` meaning stdin.
+    pub fn from_arg(arg: Option<&str>) -> Result<Input> {
+        Ok(match arg {
+            None => Input::Default,
+            Some("-") => {
+                let mut s = String::new();
+                io::stdin().read_to_string(&mut s)?;
+                Input::Text(s)
+            }
+            Some(path) => Input::Path(path.into()),
+        })
+    }
+
+    pub fn data(&self, day: usize) -> AocData {
+        match self {
+            Input::Default => AocData::new(day),
+            Input::Path(path) => AocData::from_path(path.clone()),
+            Input::Text(s) => AocData::from_string(s.clone()),
+        }
+    }
+}
+
+pub fn solve_day(day: usize, input: &Input, part: Option<Part>) -> Result<(Answers, Timing)> {
+    solve(day, input.data(day), part)
+}
+
+/// Runs a day on one of its examples. Unless a part is requested, only the
+/// parts with a known answer are solved.
+pub fn solve_example(
+    day: usize,
+    example: &Example,
+    part: Option<Part>,
+) -> Result<(Answers, Timing)> {
+    let part = part.or(match (example.first, example.second) {
+        (Some(_), None) => Some(Part::First),
+        (None, Some(_)) => Some(Part::Second),
+        _ => None,
+    });
+    solve(day, AocData::from_str(example.input), part)
+}
+
+/// Like `solve_day`, but turns a panic into an error, so that a failing
+/// day doesn't take the others down with it.
+fn try_solve_day(day: usize, input: &Input, part: Option<Part>) -> Result<(Answers, Timing)> {
+    panic::catch_unwind(|| solve_day(day, input, part))
+        .unwrap_or_else(|payload| bail!("Panicked: {}", panic_message(&*payload)))
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "unknown cause"
+    }
+}
+
+fn solve(day: usize, data: AocData, part: Option<Part>) -> Result<(Answers, Timing)> {
+    let data = data.with_part(part);
+    let start = Instant::now();
+    let result = (DAYS[day - 1].run)(&data);
+    let total = start.elapsed();
+    let parse = data.parse_time();
+    // Input errors are usually the reason the solution failed, so they
+    // take precedence over its own error.
+    data.ok()?;
+    let answers = result?.select(part);
+    let timing = Timing {
+        parse,
+        solve: total - parse.min(total),
+    };
+    Ok((answers, timing))
+}
+
+/// Solves the given days on `jobs` worker threads (0 meaning one per CPU),
+/// passing each result to `f` in the order of `days`. A day which panics
+/// fails with an error.
+pub fn solve_days<F>(
+    days: &[usize],
+    input: &Input,
+    part: Option<Part>,
+    jobs: usize,
+    mut f: F,
+) -> Result<()>
+where
+    F: FnMut(usize, Result<(Answers, Timing)>),
+{
+    if jobs == 1 {
+        for &day in days {
+            f(day, try_solve_day(day, input, part));
+        }
+        return Ok(());
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(jobs).build()?;
+    let (tx, rx) = mpsc::channel();
+    for (i, &day) in days.iter().enumerate() {
+        let tx = tx.clone();
+        let input = input.clone();
+        pool.spawn(move || {
+            let _ = tx.send((i, try_solve_day(day, &input, part)));
+        });
+    }
+    drop(tx);
+
+    let mut pending = BTreeMap::new();
+    let mut next = 0;
+    for (i, result) in rx {
+        pending.insert(i, result);
+        while let Some(result) = pending.remove(&next) {
+            f(days[next], result);
+            next += 1;
+        }
+    }
+    Ok(())
+}
+
+struct BenchResult {
+    parse: Duration,
+    solve: Duration,
+    min: Duration,
+    median: Duration,
+    max: Duration,
+}
+
+fn bench_day(day: usize, input: &Input, part: Option<Part>, runs: usize) -> Result<BenchResult> {
+    let mut timings = Vec::with_capacity(runs);
+    for _ in 0..runs {
+        timings.push(solve_day(day, input, part)?.1);
+    }
+    let median = |mut v: Vec<Duration>| {
+        v.sort();
+        v[v.len() / 2]
+    };
+    let totals: Vec<Duration> = timings.iter().map(Timing::total).collect();
+    Ok(BenchResult {
+        parse: median(timings.iter().map(|t| t.parse).collect()),
+        solve: median(timings.iter().map(|t| t.solve).collect()),
+        min: totals.iter().cloned().min().unwrap(),
+        median: median(totals.clone()),
+        max: totals.iter().cloned().max().unwrap(),
+    })
+}
+
+pub fn fmt_ms(d: Duration) -> String {
+    format!("{:.3}ms", d.as_secs_f64() * 1000.0)
+}
+
+fn print_bench_row(label: &str, r: &BenchResult) {
+    println!(
+        "{:>5} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        label,
+        fmt_ms(r.parse),
+        fmt_ms(r.solve),
+        fmt_ms(r.min),
+        fmt_ms(r.median),
+        fmt_ms(r.max)
+    );
+}
+
+pub fn bench(days: &[usize], input: &Input, part: Option<Part>, runs: usize) -> Result<()> {
+    if runs == 0 {
+        bail!("Need at least one run per day");
+    }
+    let start = Instant::now();
+    println!(
+        "{:>5} {:>12} {:>12} {:>12} {:>12} {:>12}",
+        "Day", "Parse", "Solve", "Min", "Median", "Max"
+    );
+    let mut total = BenchResult {
+        parse: Duration::default(),
+        solve: Duration::default(),
+        min: Duration::default(),
+        median: Duration::default(),
+        max: Duration::default(),
+    };
+    for &day in days {
+        let r = bench_day(day, input, part, runs)?;
+        print_bench_row(&day.to_string(), &r);
+        total.parse += r.parse;
+        total.solve += r.solve;
+        total.min += r.min;
+        total.median += r.median;
+        total.max += r.max;
+    }
+    print_bench_row("Total", &total);
+    println!(
+        "Wall time: {} ({} runs per day)",
+        fmt_ms(start.elapsed()),
+        runs
+    );
+    Ok(())
+}
+
+/// Runs the examples registered for the given days and checks their answers.
+/// Examples without a known answer for the requested part are skipped.
+pub fn examples(days: &[usize], part: Option<Part>) -> Result<()> {
+    let mut failed = vec![];
+    for &day in days {
+        for (i, example) in DAYS[day - 1].examples.iter().enumerate() {
+            if !example.covers(part) {
+                continue;
+            }
+            let label = format!("Day {} example {}", day, i + 1);
+            match solve_example(day, example, part) {
+                Ok((answers, _)) => {
+                    println!("{}: {}", label, answers);
+                    let diffs = check_example(example, &answers, part);
+                    for diff in &diffs {
+                        println!("  Mismatch in {}", diff);
+                    }
+                    if !diffs.is_empty() {
+                        failed.push(label);
+                    }
+                }
+                Err(err) => {
+                    println!("{}: error: {}", label, err);
+                    failed.push(label);
+                }
+            }
+        }
+    }
+    if !failed.is_empty() {
+        bail!("Failed: {}", failed.join(", "));
+    }
+    Ok(())
+}
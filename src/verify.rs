@@ -0,0 +1,160 @@
+use crate::prelude::*;
+use std::collections::HashMap;
+
+pub const ANSWERS_PATH: &str = "data/answers.txt";
+
+struct Expected {
+    first: String,
+    second: Option<String>,
+}
+
+/// Expected answers per day, stored as tab separated `day first [second]`
+/// lines. Newlines, tabs and backslashes in answers are escaped as `\n`,
+/// `\t` and `\\`.
+pub struct ExpectedAnswers(HashMap<usize, Expected>);
+
+impl ExpectedAnswers {
+    pub fn load(path: &str) -> Result<ExpectedAnswers> {
+        ExpectedAnswers::parse(path, &read_file(path)?)
+    }
+
+    fn parse(path: &str, text: &str) -> Result<ExpectedAnswers> {
+        let mut map = HashMap::new();
+        for (i, line) in text.lines().enumerate() {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.split('\t');
+            let day: usize = match parts.next().map(str::parse) {
+                Some(Ok(day)) => day,
+                _ => bail!("{}:{}: Failed to parse day", path, i + 1),
+            };
+            let first = match parts.next() {
+                Some(first) => unescape(first),
+                None => bail!("{}:{}: Missing first answer", path, i + 1),
+            };
+            let second = parts.next().map(unescape);
+            if parts.next().is_some() {
+                bail!("{}:{}: Too many fields", path, i + 1);
+            }
+            map.insert(day, Expected { first, second });
+        }
+        Ok(ExpectedAnswers(map))
+    }
+
+    /// Returns a description of each difference between the expected and
+    /// the actual answers of a day, ignoring the part that wasn't asked for.
+    pub fn check(&self, day: usize, answers: &Answers, part: Option<Part>) -> Vec<String> {
+        let expected = match self.0.get(&day) {
+            Some(expected) => expected,
+            None => return vec!["no expected answers recorded".to_string()],
+        };
+        let wants = |p| part.is_none() || part == Some(p);
+        let mut diffs = vec![];
+        if wants(Part::First) {
+            diffs.extend(compare(
+                "first",
+                Some(&expected.first),
+                answers.first().map(Answer::as_str),
+            ));
+        }
+        if wants(Part::Second) {
+            diffs.extend(compare(
+                "second",
+                expected.second.as_deref(),
+                answers.second().map(Answer::as_str),
+            ));
+        }
+        diffs
+    }
+}
+
+/// Compares the answers of an example run with the ones it is known to
+/// produce. Parts without a known answer or that weren't asked for are not
+/// checked.
+pub fn check_example(example: &Example, answers: &Answers, part: Option<Part>) -> Vec<String> {
+    let wants = |p| part.is_none() || part == Some(p);
+    let mut diffs = vec![];
+    if wants(Part::First) && example.first.is_some() {
+        diffs.extend(compare(
+            "first",
+            example.first,
+            answers.first().map(Answer::as_str),
+        ));
+    }
+    if wants(Part::Second) && example.second.is_some() {
+        diffs.extend(compare(
+            "second",
+            example.second,
+            answers.second().map(Answer::as_str),
+        ));
+    }
+    diffs
+}
+
+fn compare(name: &str, expected: Option<&str>, actual: Option<&str>) -> Option<String> {
+    if expected == actual {
+        return None;
+    }
+    Some(format!(
+        "{} answer:\n    - {}\n    + {}",
+        name,
+        expected.map(escape).unwrap_or_else(|| "<none>".into()),
+        actual.map(escape).unwrap_or_else(|| "<none>".into())
+    ))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(s: &str) -> String {
+    let mut result = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some(c) => result.push(c),
+                None => result.push('\\'),
+            }
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        for s in &["a\nb", "a\\nb", "\\", "a\tb", "\t\\t\n\\"] {
+            assert_eq!(unescape(&escape(s)), *s);
+            assert!(!escape(s).contains(&['\n', '\t'][..]));
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let answers =
+            ExpectedAnswers::parse("answers.txt", "# day\tfirst\n1\t12\ta\\nb\n").unwrap();
+        let expected = &answers.0[&1];
+        assert_eq!(expected.first, "12");
+        assert_eq!(expected.second.as_deref(), Some("a\nb"));
+
+        let err = ExpectedAnswers::parse("answers.txt", "1\t12\nx\t3")
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "answers.txt:2: Failed to parse day");
+        let err = ExpectedAnswers::parse("answers.txt", "1\t2\t3\t4")
+            .err()
+            .unwrap();
+        assert_eq!(err.to_string(), "answers.txt:1: Too many fields");
+    }
+}